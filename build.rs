@@ -4,12 +4,15 @@ use std::path::{Path, PathBuf};
 use std::process::{self, Command};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+// version of the vendored honggfuzz, when its sources are not there to tell
+const HONGGFUZZ_VERSION: &str = "2.6";
 
 #[cfg(target_family = "windows")]
 compile_error!(
     "honggfuzz-rs does not currently support Windows but works well under WSL (Windows Subsystem for Linux)"
 );
 
+// the `honggfuzz` command is still built with honggfuzz's Makefile
 // TODO: maybe use `make-cmd` crate
 #[cfg(not(any(
     target_os = "freebsd",
@@ -26,6 +29,9 @@ const GNU_MAKE: &str = "make";
 ))]
 const GNU_MAKE: &str = "gmake";
 
+// written next to the `honggfuzz` command when it is built with the `minimal-driver` feature, read by cargo-hfuzz
+const MINIMAL_DRIVER_FILENAME: &str = "honggfuzz.minimal";
+
 fn main() {
     // Only build honggfuzz binaries if we are in the process of building an instrumented binary
     let honggfuzz_target = match env::var("CARGO_HONGGFUZZ_TARGET_DIR") {
@@ -34,6 +40,13 @@ fn main() {
         Err(_) => return,
     };
 
+    // `cc` tells cargo which variables to watch, which replaces the default of running again on any change
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=honggfuzz");
+    for var in ["CARGO_HONGGFUZZ_TARGET_DIR", "CARGO_HONGGFUZZ_BUILD_VERSION", "CRATE_ROOT", "HFUZZ_HONGGFUZZ_DIR", "HFUZZ_CACHE_DIR"] {
+        println!("cargo:rerun-if-env-changed={var}");
+    }
+
     // check that "cargo hfuzz" command is at the same version as this file
     let honggfuzz_build_version =
         env::var("CARGO_HONGGFUZZ_BUILD_VERSION").unwrap_or("unknown".to_string());
@@ -52,44 +65,428 @@ fn main() {
     let honggfuzz_target = Path::new(&env::var("CRATE_ROOT").unwrap()) // from honggfuzz
         .join(honggfuzz_target); // resolve the original honggfuzz_target relative to CRATE_ROOT
 
-    let build_dir = out_dir.join("honggfuzz");
+    let minimal_driver_marker = honggfuzz_target.join(MINIMAL_DRIVER_FILENAME);
+    let _ = fs::remove_file(&minimal_driver_marker);
+
+    // use an existing honggfuzz install instead of building the vendored one
+    if let Some(install_dir) = system_honggfuzz() {
+        link_system_honggfuzz(&install_dir, &honggfuzz_target);
+        return;
+    }
+
+    if !Path::new("honggfuzz/Makefile").exists() {
+        eprintln!(
+            "The honggfuzz sources are missing from \"{}/honggfuzz\".\n\
+             If honggfuzz-rs is a git checkout, run `git submodule update --init`.",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        process::exit(1);
+    }
+
+    let minimal_driver = cfg!(target_os = "linux") && env::var_os("CARGO_FEATURE_MINIMAL_DRIVER").is_some();
+    let variables: &[&str] = if minimal_driver {
+        // no stack unwinding nor disassembly of crashes: they are told apart by their program counter only
+        &[
+            "ARCH_CFLAGS=-D_FILE_OFFSET_BITS=64 -D_HF_LINUX_NO_BFD -D_HF_LINUX_NO_UNWIND",
+            "ARCH_LDFLAGS=-lpthread -lrt -ldl",
+        ]
+    } else {
+        &[]
+    };
+
+    // the same runtime is shared by every project and target directory
+    let cached = cache_dir().map(|dir| dir.join(runtime_key(variables)));
+    let restored = match cached.as_deref().filter(|cached| cached.is_dir()) {
+        Some(cached) => match restore_from_cache(cached, &out_dir, &honggfuzz_target) {
+            Ok(()) => true,
+            Err(e) => {
+                // partly deleted or corrupted, built again and stored anew below
+                println!("cargo:warning=ignoring the honggfuzz runtime cached in \"{}\": {}", cached.display(), e);
+                let _ = fs::remove_dir_all(cached);
+                false
+            }
+        },
+        None => false,
+    };
+    if restored {
+        println!("cargo:rustc-link-lib=static=hfuzz");
+        println!("cargo:rustc-link-lib=static=hfcommon");
+        println!("cargo:rustc-link-search=native={}", out_dir.display());
+    } else {
+        // the static libraries are linked in the fuzzed binary so they are built for the target
+        // (`hfuzz` first, it uses `hfcommon`), cargo is told how to link them by `cc`
+        build_library("hfuzz", "libhfuzz");
+        build_library("hfcommon", "libhfcommon");
+
+        // the honggfuzz command runs on the host
+        let build_dir = out_dir.join("honggfuzz");
+        make(&build_dir, variables, &["honggfuzz"]);
+        fs::copy(build_dir.join("honggfuzz"), honggfuzz_target.join("honggfuzz")).unwrap();
+
+        if let Some(cached) = cached {
+            let files = [out_dir.join("libhfuzz.a"), out_dir.join("libhfcommon.a"), build_dir.join("honggfuzz")];
+            if let Err(e) = store_in_cache(&cached, &files) {
+                println!("cargo:warning=could not cache the honggfuzz runtime in \"{}\": {}", cached.display(), e);
+            }
+        }
+    }
+    if minimal_driver {
+        fs::write(&minimal_driver_marker, "").unwrap();
+    }
+}
+
+// `HFUZZ_CACHE_DIR`, the cache is opt-in as it is written outside of the target directory
+fn cache_dir() -> Option<PathBuf> {
+    env::var_os("HFUZZ_CACHE_DIR").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+}
+
+// name of the cache entry of the runtime built with the current compilers and flags
+fn runtime_key(variables: &[&str]) -> String {
+    let mut key = format!(
+        "honggfuzz-rs {VERSION}\nhonggfuzz {}\n{}\n{}\n",
+        honggfuzz_version(),
+        env::var("TARGET").unwrap(),
+        env::var("HOST").unwrap()
+    );
+
+    // libraries, with the compiler and flags chosen by `cc`
+    let compiler = runtime_build().get_compiler();
+    key += &format!("{}\n", compiler.path().display());
+    for arg in compiler.args() {
+        key += &format!("{}\n", arg.to_string_lossy());
+    }
+    key += &compiler_version(compiler.path().as_os_str());
+
+    // honggfuzz command, built by make with the host compiler
+    let host_cc = env::var_os("CC").unwrap_or_else(|| "cc".into());
+    key += &compiler_version(&host_cc);
+    for var in ["CC", "CFLAGS", "LDFLAGS"] {
+        key += &format!("{var}={}\n", env::var(var).unwrap_or_default());
+    }
+    key += &variables.join("\n");
+
+    // the sources, which may change without a new honggfuzz version (e.g. a submodule update)
+    let mut sources = source_files();
+    sources.sort();
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, key.as_bytes());
+    for source in sources {
+        hash = fnv1a(hash, source.to_string_lossy().as_bytes());
+        hash = fnv1a(hash, &fs::read(&source).unwrap_or_default());
+    }
+    format!("{hash:016x}")
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+// FNV-1a, stable across Rust versions unlike `DefaultHasher`
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3))
+}
+
+// sources of the runtime: the libraries, the driver with its per-OS directories, and the Makefile building them
+fn source_files() -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from("honggfuzz/Makefile")];
+    for dir in ["", "libhfuzz", "libhfcommon", "linux", "mac", "netbsd", "posix"] {
+        files.extend(
+            fs::read_dir(Path::new("honggfuzz").join(dir))
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "c" || ext == "h")),
+        );
+    }
+    files
+}
+
+fn compiler_version(compiler: &std::ffi::OsStr) -> String {
+    Command::new(compiler)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+// copy the libraries and the honggfuzz command of the cache entry `cached` where the build expects them
+fn restore_from_cache(cached: &Path, out_dir: &Path, honggfuzz_target: &Path) -> std::io::Result<()> {
+    for library in ["libhfuzz.a", "libhfcommon.a"] {
+        let _ = fs::remove_file(out_dir.join(library));
+        fs::copy(cached.join(library), out_dir.join(library))?;
+    }
+    fs::copy(cached.join("honggfuzz"), honggfuzz_target.join("honggfuzz"))?;
+    Ok(())
+}
+
+// copy `files` in the cache entry `cached`, which appears at once so that concurrent builds only see complete entries
+fn store_in_cache(cached: &Path, files: &[PathBuf]) -> std::io::Result<()> {
+    let partial = cached.with_extension(format!("partial-{}", process::id()));
+    fs::create_dir_all(&partial)?;
+    for file in files {
+        fs::copy(file, partial.join(file.file_name().unwrap()))?;
+    }
+    if fs::rename(&partial, cached).is_err() {
+        // stored by another build in the meantime
+        fs::remove_dir_all(&partial)?;
+    }
+    Ok(())
+}
+
+// compile the C files of `honggfuzz/<dir>` into the static library `lib<name>.a` with the `cc` crate,
+// which follows `CC_<target>`, `CFLAGS_<target>` ... and cargo's jobserver
+fn build_library(name: &str, dir: &str) {
+    let mut sources: Vec<PathBuf> = fs::read_dir(Path::new("honggfuzz").join(dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    sources.sort();
+
+    runtime_build()
+        .files(&sources)
+        .warnings(false)
+        .try_compile(name)
+        .unwrap_or_else(|e| {
+            // the compiler output is above, in the warnings forwarded by `cc`
+            eprintln!("Failed to build {dir} from the honggfuzz sources: {e}");
+            process::exit(1);
+        });
+}
+
+// every `-fsanitize-coverage` feature `cargo hfuzz build --instrument-c` may pass,
+// the clang names of `honggfuzz::config::COVERAGE_FEATURES` and `edge`
+const CLANG_COVERAGE_FEATURES: &[&str] = &[
+    "edge",
+    "indirect-calls",
+    "trace-pc-guard",
+    "trace-pc",
+    "inline-8bit-counters",
+    "inline-bool-flag",
+    "pc-table",
+    "trace-cmp",
+    "trace-div",
+    "trace-gep",
+    "trace-loads",
+    "trace-stores",
+    "stack-depth",
+];
+
+// compiler and flags of the honggfuzz runtime
+fn runtime_build() -> cc::Build {
+    // same as `-D_HF_ARCH_${ARCH}` in honggfuzz's Makefile
+    let arch = match env::var("CARGO_CFG_TARGET_OS").unwrap().as_str() {
+        "linux" | "android" => "LINUX",
+        "macos" => "DARWIN",
+        "netbsd" => "NETBSD",
+        _ => "POSIX",
+    };
+
+    let mut build = cc::Build::new();
+    build
+        .include("honggfuzz")
+        .define("_GNU_SOURCE", None)
+        .define(&format!("_HF_ARCH_{arch}"), None)
+        .define("_FILE_OFFSET_BITS", "64")
+        .std("c11")
+        .flag("-fno-builtin")
+        .flag("-fno-stack-protector")
+        // `cargo hfuzz build --instrument-c` compiles with coverage flags,
+        // the runtime must not call its own coverage callbacks
+        .flag_if_supported(format!("-fno-sanitize-coverage={}", CLANG_COVERAGE_FEATURES.join(",")));
+    build
+}
+
+// run honggfuzz's Makefile to build `targets` in `build_dir`, overriding some of its `variables`
+fn make(build_dir: &Path, variables: &[&str], targets: &[&str]) {
+    fs::create_dir_all(build_dir).unwrap();
+
     let build_dir_str = build_dir.to_str().unwrap();
-    let make_arg_build_dir = format!("BUILD_DIR={build_dir_str}");
-    let make_arg_honggfuzz = format!("{build_dir_str}/honggfuzz");
-    let make_arg_libhfuzz = format!("{build_dir_str}/libhfuzz/libhfuzz.a");
-    let make_arg_libhfcommon = format!("{build_dir_str}/libhfcommon/libhfcommon.a");
-
-    fs::create_dir_all(&build_dir).unwrap();
-
-    // build honggfuzz command and hfuzz static library
-    let status = Command::new(GNU_MAKE)
-        .args(["-C", "honggfuzz", &make_arg_build_dir, &make_arg_honggfuzz, &make_arg_libhfuzz, &make_arg_libhfcommon])
-        .status()
-        .unwrap_or_else(|_e| panic!("failed to run \"{GNU_MAKE} -C honggfuzz {make_arg_build_dir} {make_arg_honggfuzz} {make_arg_libhfuzz} {make_arg_libhfcommon}"));
-    assert!(status.success());
-
-    let _ = fs::remove_file(out_dir.join("libhfuzz.a"));
-    fs::hard_link(
-        build_dir.join("libhfuzz/libhfuzz.a"),
-        out_dir.join("libhfuzz.a"),
-    )
-    .unwrap();
-
-    let _ = fs::remove_file(out_dir.join("libhfcommon.a"));
-    fs::hard_link(
-        build_dir.join("libhfcommon/libhfcommon.a"),
-        out_dir.join("libhfcommon.a"),
-    )
-    .unwrap();
-
-    fs::copy(
-        build_dir.join("honggfuzz"),
-        honggfuzz_target.join("honggfuzz"),
-    )
-    .unwrap();
-
-    // tell cargo how to link final executable to hfuzz static library
+    let mut args = vec!["-C".to_string(), "honggfuzz".to_string(), format!("BUILD_DIR={build_dir_str}")];
+    args.extend(variables.iter().map(ToString::to_string));
+    args.extend(targets.iter().map(|target| format!("{build_dir_str}/{target}")));
+
+    let mut command = Command::new(GNU_MAKE);
+    command.args(&args);
+    // share cargo's jobserver
+    if let Ok(makeflags) = env::var("CARGO_MAKEFLAGS") {
+        command.env("MAKEFLAGS", makeflags);
+    }
+    let output = command
+        .output()
+        .unwrap_or_else(|_e| panic!("failed to run \"{GNU_MAKE} {}\"", args.join(" ")));
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        eprint!("{}{}", String::from_utf8_lossy(&output.stdout), stderr);
+        eprintln!("\"{GNU_MAKE} {}\" failed", args.join(" "));
+        if let Some(hint) = missing_dependency(&stderr) {
+            eprintln!("{hint}");
+            if variables.is_empty() && cfg!(target_os = "linux") {
+                eprintln!(
+                    "libbfd, libunwind and liblzma are not needed with the `minimal-driver` feature of honggfuzz, \
+                     see `cargo hfuzz version` for what it changes."
+                );
+            }
+        }
+        process::exit(1);
+    }
+}
+
+// system libraries used by honggfuzz, as named in its headers and link flags, and their Debian/Ubuntu packages
+const SYSTEM_DEPENDENCIES: &[(&str, &str, &str)] = &[
+    ("libunwind", "libunwind", "libunwind-dev"),
+    ("bfd", "bfd", "binutils-dev"),
+    ("dis-asm", "opcodes", "binutils-dev"),
+    ("lzma", "lzma", "liblzma-dev"),
+    ("Block", "BlocksRuntime", "libblocksruntime-dev"),
+];
+
+// name the missing system header or library in the output of a failed C build
+fn missing_dependency(output: &str) -> Option<String> {
+    for line in output.lines() {
+        // gcc: "fatal error: bfd.h: No such file or directory", clang: "fatal error: 'bfd.h' file not found"
+        if let Some((_, rest)) = line.split_once("fatal error: ") {
+            let header = rest.trim_start_matches('\'').split([':', '\'']).next().unwrap_or_default();
+            let package = SYSTEM_DEPENDENCIES
+                .iter()
+                .find(|(prefix, _, _)| header.starts_with(prefix))
+                .map(|(_, _, package)| format!(", install it with your system's package manager (e.g. `{package}`)"))
+                .unwrap_or_default();
+            return Some(format!("The system header `{header}` is missing{package}."));
+        }
+        // ld: "cannot find -llzma"
+        if let Some((_, rest)) = line.split_once("cannot find -l") {
+            let library = rest.split_whitespace().next().unwrap_or_default();
+            let package = SYSTEM_DEPENDENCIES
+                .iter()
+                .find(|(_, prefix, _)| library.starts_with(prefix))
+                .map(|(_, _, package)| format!(", install it with your system's package manager (e.g. `{package}`)"))
+                .unwrap_or_default();
+            return Some(format!("The system library `lib{library}` is missing{package}."));
+        }
+    }
+    None
+}
+
+// honggfuzz install given by `HFUZZ_HONGGFUZZ_DIR`, or found in `PATH` with the `system-honggfuzz` feature
+fn system_honggfuzz() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("HFUZZ_HONGGFUZZ_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    env::var_os("CARGO_FEATURE_SYSTEM_HONGGFUZZ")?;
+
+    let honggfuzz = env::split_paths(&env::var_os("PATH").unwrap_or_default())
+        .map(|dir| dir.join("honggfuzz"))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| {
+            eprintln!(
+                "The `system-honggfuzz` feature is enabled but no `honggfuzz` executable was found in PATH.\n\
+                 Install honggfuzz or set HFUZZ_HONGGFUZZ_DIR to its installation directory."
+            );
+            process::exit(1);
+        });
+    // `<prefix>/bin/honggfuzz`, or the honggfuzz build directory itself
+    let dir = honggfuzz.parent().unwrap();
+    match dir.file_name() {
+        Some(name) if name == "bin" => dir.parent().map(Path::to_path_buf),
+        _ => Some(dir.to_path_buf()),
+    }
+}
+
+// link the static libraries of an install (`<prefix>/lib`) or build directory of honggfuzz,
+// and copy its `honggfuzz` executable where cargo-hfuzz expects it
+fn link_system_honggfuzz(install_dir: &Path, honggfuzz_target: &Path) {
+    let find = |candidates: &[PathBuf]| -> PathBuf {
+        candidates.iter().find(|path| path.is_file()).cloned().unwrap_or_else(|| {
+            eprintln!(
+                "Could not find {} in the honggfuzz installation \"{}\", looked for:",
+                candidates[0].file_name().unwrap().to_string_lossy(),
+                install_dir.display()
+            );
+            for candidate in candidates {
+                eprintln!("- {}", candidate.display());
+            }
+            process::exit(1);
+        })
+    };
+    let library = |name: &str, build_subdir: &str| {
+        let file = format!("lib{}.a", name);
+        find(&[
+            install_dir.join("lib").join(&file),
+            install_dir.join("lib64").join(&file),
+            install_dir.join(build_subdir).join(&file),
+        ])
+    };
+
+    let honggfuzz = find(&[install_dir.join("bin/honggfuzz"), install_dir.join("honggfuzz")]);
+    check_honggfuzz_version(&honggfuzz);
+
+    let libhfuzz = library("hfuzz", "libhfuzz");
+    let libhfcommon = library("hfcommon", "libhfcommon");
+
+    fs::copy(&honggfuzz, honggfuzz_target.join("honggfuzz")).unwrap();
+    for path in [&honggfuzz, &libhfuzz, &libhfcommon] {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+
     println!("cargo:rustc-link-lib=static=hfuzz");
     println!("cargo:rustc-link-lib=static=hfcommon");
-    println!("cargo:rustc-link-search=native={}", out_dir.display());
+    println!("cargo:rustc-link-search=native={}", libhfuzz.parent().unwrap().display());
+    if libhfcommon.parent() != libhfuzz.parent() {
+        println!("cargo:rustc-link-search=native={}", libhfcommon.parent().unwrap().display());
+    }
+}
+
+// the vendored version, read from its sources when they are there
+fn honggfuzz_version() -> String {
+    fs::read_to_string("honggfuzz/honggfuzz.h")
+        .ok()
+        .and_then(|header| {
+            header
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("#define PROG_VERSION"))
+                .map(|version| version.trim().trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| HONGGFUZZ_VERSION.to_string())
+}
+
+// check that a system honggfuzz is the version this crate is written against,
+// the same way the version of cargo-hfuzz is checked
+fn check_honggfuzz_version(honggfuzz: &Path) {
+    let expected = honggfuzz_version();
+    let output = Command::new(honggfuzz).arg("--version").output();
+    let found = output.ok().and_then(|output| {
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        // e.g. "honggfuzz version 2.6"
+        text.split_whitespace()
+            .map(|word| word.trim_start_matches('v'))
+            .find(|word| {
+                word.split('.').count() >= 2
+                    && word.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+            })
+            .map(String::from)
+    });
+    match found {
+        Some(found) if found == expected => {}
+        Some(found) => {
+            eprintln!(
+                "The honggfuzz installation \"{}\" is version {}, but this version of honggfuzz-rs ({}) works with honggfuzz {}.\n\
+                 Install honggfuzz {} or unset HFUZZ_HONGGFUZZ_DIR to use the vendored one.",
+                honggfuzz.display(),
+                found,
+                VERSION,
+                expected,
+                expected
+            );
+            process::exit(1);
+        }
+        None => println!(
+            "cargo:warning=could not tell the version of \"{}\", expected honggfuzz {}",
+            honggfuzz.display(),
+            expected
+        ),
+    }
 }
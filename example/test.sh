@@ -25,7 +25,8 @@ mkdir -p $workspace/input
 
 # fuzz example
 cd subdirectory
-HFUZZ_RUN_ARGS="-v -N 10000000 --run_time 120 -F3 --exit_upon_crash" $CARGO_HFUZZ run example
+HFUZZ_RUN_ARGS="-v -N 10000000 --run_time 120 -F3 --exit_upon_crash" $CARGO_HFUZZ run --stats-file example
+$CARGO_HFUZZ stats example
 cd ..
 
 # build example without instrumentation
@@ -65,6 +66,16 @@ status=$?
 set -e
 test $status -eq 2
 
+# triage the crashes found, they should all be grouped under the panic location
+cd subdirectory
+RUSTFLAGS="" $CARGO_HFUZZ triage example --json | grep -q '"location":"src/main.rs:'
+cd ..
+
+# the crash should also be listed in the honggfuzz report
+cd subdirectory
+$CARGO_HFUZZ report example | grep -q '"crash_file":'
+cd ..
+
 # run `hfuzz clean` from a subdirectory just to check that hfuzz subcommands are run at the crate root
 cd subdirectory
 $CARGO_HFUZZ clean
@@ -1,13 +1,31 @@
+use honggfuzz::bundle::{self, BundleInfo};
+use honggfuzz::campaign::{self, Campaign};
+use honggfuzz::ci::{self, Check};
+use honggfuzz::config::{self, Config, TargetConfig};
+use honggfuzz::corpus::{self, Engine};
+use honggfuzz::report;
+use honggfuzz::stats;
+use honggfuzz::triage::{self, Crash, Outcome, PanicInfo};
 use rustc_version::Channel;
 use std::env;
 use std::fs;
-use std::os::unix::process::CommandExt;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::{Path, PathBuf};
 use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const HONGGFUZZ_TARGET: &str = "hfuzz_target";
 const HONGGFUZZ_WORKSPACE: &str = "hfuzz_workspace";
+// written next to instrumented binaries
+const COVERAGE_FILENAME: &str = "hfuzz-coverage.txt";
+// written by build.rs next to the `honggfuzz` command built with the `minimal-driver` feature
+const MINIMAL_DRIVER_FILENAME: &str = "honggfuzz.minimal";
+// seconds before a replay is killed when the target has no `timeout` setting
+const DEFAULT_REPLAY_TIMEOUT: u64 = 10;
 
 #[cfg(target_family = "windows")]
 compile_error!(
@@ -22,8 +40,311 @@ enum BuildType {
     Debug,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum Sanitizer {
+    Address,
+    Thread,
+    Leak,
+    Memory,
+}
+
+impl Sanitizer {
+    fn from_name(name: &str) -> Option<Sanitizer> {
+        match name {
+            "address" => Some(Sanitizer::Address),
+            "thread" => Some(Sanitizer::Thread),
+            "leak" => Some(Sanitizer::Leak),
+            "memory" => Some(Sanitizer::Memory),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Sanitizer::Address => "address",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Leak => "leak",
+            Sanitizer::Memory => "memory",
+        }
+    }
+}
+
+/// Kinds of cargo targets that can be fuzzed.
+#[derive(Clone, Copy, PartialEq)]
+enum TargetKind {
+    Bin,
+    Example,
+    Test,
+    Bench,
+}
+
+impl TargetKind {
+    const ALL: [TargetKind; 4] = [
+        TargetKind::Bin,
+        TargetKind::Example,
+        TargetKind::Test,
+        TargetKind::Bench,
+    ];
+
+    // cargo option selecting a target of this kind
+    fn cargo_option(&self) -> &'static str {
+        match *self {
+            TargetKind::Bin => "--bin",
+            TargetKind::Example => "--example",
+            TargetKind::Test => "--test",
+            TargetKind::Bench => "--bench",
+        }
+    }
+
+    // name of this kind in the JSON messages of cargo
+    fn name(&self) -> &'static str {
+        match *self {
+            TargetKind::Bin => "bin",
+            TargetKind::Example => "example",
+            TargetKind::Test => "test",
+            TargetKind::Bench => "bench",
+        }
+    }
+}
+
+/// A cargo target built and fuzzed by cargo-hfuzz.
+#[derive(Clone)]
+struct FuzzTarget {
+    kind: TargetKind,
+    name: String,
+}
+
+impl FuzzTarget {
+    fn bin(name: String) -> FuzzTarget {
+        FuzzTarget {
+            kind: TargetKind::Bin,
+            name,
+        }
+    }
+
+    // consume `--bin NAME`, `--example NAME` ... from `arg` and `args`
+    fn parse_arg<T>(arg: &str, args: &mut T) -> Option<FuzzTarget>
+    where
+        T: std::iter::Iterator<Item = String>,
+    {
+        TargetKind::ALL.into_iter().find_map(|kind| {
+            option_value(arg, kind.cargo_option(), args).map(|name| FuzzTarget { kind, name })
+        })
+    }
+
+    // arguments given to `cargo build` to build only this target
+    fn build_args(&self) -> Vec<String> {
+        vec![self.kind.cargo_option().to_string(), self.name.clone()]
+    }
+
+    // path of the executable cargo reported for this target in `artifacts`
+    fn executable(&self, artifacts: &[Artifact]) -> PathBuf {
+        let artifact = artifacts
+            .iter()
+            .find(|artifact| artifact.kind == self.kind && artifact.name == self.name)
+            .unwrap_or_else(|| {
+                eprintln!(
+                    "cargo did not build an executable for {} {}",
+                    self.kind.cargo_option(),
+                    self.name
+                );
+                process::exit(1);
+            });
+        artifact.executable.clone()
+    }
+}
+
+/// Executable built by cargo, as reported by `--message-format=json`.
+struct Artifact {
+    kind: TargetKind,
+    name: String,
+    executable: PathBuf,
+}
+
+impl Artifact {
+    // parse a `compiler-artifact` message of cargo, `None` for other messages
+    // and for artifacts that are not executables (libraries, build scripts ...)
+    fn from_message(message: &str) -> Option<Artifact> {
+        if !message.contains("\"reason\":\"compiler-artifact\"") {
+            return None;
+        }
+        let kind = json_string_after(message, "\"kind\":[")?;
+        Some(Artifact {
+            kind: TargetKind::ALL.into_iter().find(|k| k.name() == kind)?,
+            // the first `name` is the one of the target
+            name: json_string_after(message, "\"name\":")?,
+            executable: PathBuf::from(json_string_after(message, "\"executable\":")?),
+        })
+    }
+}
+
+// unescaped string value right after the first `prefix` in a JSON message,
+// `None` if it is not a string (like a `null` executable)
+fn json_string_after(json: &str, prefix: &str) -> Option<String> {
+    let start = json.find(prefix)? + prefix.len();
+    let mut chars = json[start..].strip_prefix('"')?.chars();
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'b' => value.push('\u{8}'),
+                'f' => value.push('\u{c}'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    value.push(
+                        u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER),
+                    );
+                }
+                escaped => value.push(escaped), // `\"`, `\\` and `\/`
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// Options of the build and run commands handled by cargo-hfuzz itself.
+#[derive(Default)]
+struct BuildOptions {
+    /// `--target TRIPLE`, defaults to the host triple
+    target: Option<String>,
+    /// `--sanitizer NAME`
+    sanitizer: Option<Sanitizer>,
+    /// `--profile NAME`, takes precedence over a `--profile` in the build arguments
+    profile: Option<String>,
+    /// `--coverage FEATURE,...`, takes precedence over the `coverage` setting
+    coverage: Option<Vec<String>>,
+    /// `--instrument-c`, like the `instrument_c` setting
+    instrument_c: bool,
+}
+
+impl BuildOptions {
+    // consume `arg`, and its value from `args`, if it is one of our options
+    fn parse_arg<T>(&mut self, arg: &str, args: &mut T) -> bool
+    where
+        T: std::iter::Iterator<Item = String>,
+    {
+        if let Some(triple) = option_value(arg, "--target", args) {
+            self.target = Some(triple);
+            return true;
+        }
+        if let Some(profile) = option_value(arg, "--profile", args) {
+            self.profile = Some(profile);
+            return true;
+        }
+        if arg == "--instrument-c" {
+            self.instrument_c = true;
+            return true;
+        }
+        if let Some(list) = option_value(arg, "--coverage", args) {
+            let spec: Vec<_> = list.split(',').map(String::from).collect();
+            if let Err(e) = config::coverage_features(&spec) {
+                eprintln!("--coverage: {}", e);
+                process::exit(1);
+            }
+            self.coverage = Some(spec);
+            return true;
+        }
+        if let Some(name) = option_value(arg, "--sanitizer", args) {
+            self.sanitizer = Some(Sanitizer::from_name(&name).unwrap_or_else(|| {
+                eprintln!(
+                    "unknown sanitizer \"{}\", possible values are: address, thread, leak, memory",
+                    name
+                );
+                process::exit(1);
+            }));
+            return true;
+        }
+        false
+    }
+
+    // split cargo build arguments between our options and the ones for cargo
+    fn from_args<T>(mut args: T) -> (BuildOptions, Vec<String>)
+    where
+        T: std::iter::Iterator<Item = String>,
+    {
+        let mut options = BuildOptions::default();
+        let mut rest = Vec::new();
+        while let Some(arg) = args.next() {
+            if !options.parse_arg(&arg, &mut args) {
+                rest.push(arg);
+            }
+        }
+        (options, rest)
+    }
+
+    fn target_triple(&self) -> String {
+        self.target.clone().unwrap_or_else(host_triple)
+    }
+
+    // cargo profile of a build: `--profile`, then the one in the build arguments,
+    // then `release` to fuzz and `dev` to debug
+    fn profile(&self, build_type: &BuildType, config: &TargetConfig) -> String {
+        self.profile
+            .clone()
+            .or_else(|| split_profile(build_args(config)).0)
+            .unwrap_or_else(|| match *build_type {
+                BuildType::Debug | BuildType::ProfileWithGrcov => "dev".into(),
+                _ => "release".into(),
+            })
+    }
+
+    // sanitizer-coverage features of instrumented builds
+    fn coverage(&self, config: &TargetConfig) -> Vec<String> {
+        let spec = self.coverage.as_ref().or(config.coverage.as_ref());
+        // both were checked when parsed
+        config::coverage_features(spec.map_or(&[], Vec::as_slice)).unwrap()
+    }
+
+    // directory holding the artifacts of a build, e.g. `hfuzz_target/<triple>/release`
+    fn profile_dir(&self, build_type: &BuildType, config: &TargetConfig) -> PathBuf {
+        let profile = self.profile(build_type, config);
+        // like cargo, the built-in profiles keep their historical directory names
+        let dir = match profile.as_str() {
+            "dev" | "test" => "debug",
+            "release" | "bench" => "release",
+            custom => custom,
+        };
+        Path::new(&self.target_dir())
+            .join(self.target_triple())
+            .join(dir)
+    }
+
+    // sanitized builds get their own target directory so that switching
+    // sanitizers does not rebuild everything
+    fn target_dir(&self) -> String {
+        let honggfuzz_target =
+            env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| HONGGFUZZ_TARGET.into());
+        match self.sanitizer {
+            Some(sanitizer) => format!("{}/sanitizer-{}", honggfuzz_target, sanitizer.name()),
+            None => honggfuzz_target,
+        }
+    }
+}
+
+// value of `--name VALUE` or `--name=VALUE`, `None` if `arg` is not `name`
+fn option_value<T>(arg: &str, name: &str, args: &mut T) -> Option<String>
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    match arg.strip_prefix(name) {
+        Some("") => Some(args.next().unwrap_or_else(|| {
+            eprintln!("{} expects a value (eg. {} <value>)", name, name);
+            process::exit(1);
+        })),
+        Some(value) => value.strip_prefix('=').map(String::from),
+        None => None,
+    }
+}
+
 // TODO: maybe use `rustc_version` crate
-fn target_triple() -> String {
+fn host_triple() -> String {
     let output = Command::new("rustc").args(["-v", "-V"]).output().unwrap();
     let stdout = String::from_utf8(output.stdout).unwrap();
     let triple = stdout
@@ -34,184 +355,2236 @@ fn target_triple() -> String {
     triple.into()
 }
 
-fn find_crate_root() -> Option<PathBuf> {
-    let mut path = env::current_dir().unwrap();
+fn find_crate_root() -> Option<PathBuf> {
+    let mut path = env::current_dir().unwrap();
+
+    while !path.join("Cargo.toml").is_file() {
+        // move to parent path
+        path = match path.parent() {
+            Some(parent) => parent.into(),
+            None => return None, // early return
+        };
+    }
+
+    Some(path)
+}
+
+// names of the binary targets of the crate, discovered the way cargo does
+fn bin_targets(crate_root: &Path) -> Vec<String> {
+    let manifest: toml::Table = fs::read_to_string(crate_root.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse().ok())
+        .unwrap_or_default();
+    let package = manifest.get("package").and_then(|package| package.as_table());
+
+    let mut targets = std::collections::BTreeSet::new();
+    for bin in manifest
+        .get("bin")
+        .and_then(|bins| bins.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(name) = bin.get("name").and_then(|name| name.as_str()) {
+            targets.insert(name.to_string());
+        }
+    }
+
+    let autobins = package
+        .and_then(|package| package.get("autobins"))
+        .and_then(toml::Value::as_bool)
+        .unwrap_or(true);
+    if autobins {
+        if crate_root.join("src/main.rs").is_file() {
+            if let Some(name) = package
+                .and_then(|package| package.get("name"))
+                .and_then(|name| name.as_str())
+            {
+                targets.insert(name.to_string());
+            }
+        }
+        for entry in fs::read_dir(crate_root.join("src/bin")).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let name = if path.extension().is_some_and(|ext| ext == "rs") {
+                path.file_stem()
+            } else if path.join("main.rs").is_file() {
+                path.file_name()
+            } else {
+                None
+            };
+            targets.extend(name.map(|name| name.to_string_lossy().into_owned()));
+        }
+    }
+
+    targets.into_iter().collect()
+}
+
+// number of frames whose locals are printed by `debugger_batch_command`,
+// enough to go through the panic machinery down to the code that panicked
+const BATCH_LOCALS_FRAMES: usize = 12;
+
+fn debugger_name() -> String {
+    env::var("HFUZZ_DEBUGGER").unwrap_or_else(|_| "rust-lldb".into())
+}
+
+fn is_lldb(debugger: &str) -> bool {
+    Path::new(debugger)
+        .file_name()
+        .is_some_and(|f| f.to_string_lossy().contains("lldb"))
+}
+
+fn debugger_command(executable: &str) -> Command {
+    let debugger = debugger_name();
+
+    let mut cmd = Command::new(&debugger);
+
+    if is_lldb(&debugger) {
+        cmd.args([
+            "-o",
+            "b rust_panic",
+            "-o",
+            "r",
+            "-o",
+            "bt",
+            "-f",
+            executable,
+            "--",
+        ]);
+    } else {
+        cmd.args([
+            "-ex",
+            "b rust_panic",
+            "-ex",
+            "r",
+            "-ex",
+            "bt",
+            "--args",
+            executable,
+        ]);
+    }
+
+    cmd
+}
+
+// like `debugger_command`, but exits after printing the backtrace and the
+// locals of the top frames, when the target panics or gets a fatal signal
+fn debugger_batch_command(executable: &str) -> Command {
+    let debugger = debugger_name();
+
+    let mut cmd = Command::new(&debugger);
+
+    if is_lldb(&debugger) {
+        let mut report = vec!["bt".to_string()];
+        for frame in 0..BATCH_LOCALS_FRAMES {
+            report.push(format!("frame select {}", frame));
+            report.push("frame variable".into());
+        }
+        cmd.args(["--batch", "-o", "b rust_panic", "-o", "r"]);
+        for command in &report {
+            cmd.args(["-o", command]);
+        }
+        // run instead of the `-o` commands when the target stops on a signal
+        for command in &report {
+            cmd.args(["-k", command]);
+        }
+        cmd.args(["-k", "quit", "-f", executable, "--"]);
+    } else {
+        // gdb stops on SIGSEGV, SIGABRT ... by default
+        cmd.args([
+            "-batch",
+            "-ex",
+            "set pagination off",
+            "-ex",
+            "b rust_panic",
+            "-ex",
+            "r",
+            "-ex",
+            "bt",
+            "-ex",
+            &format!("bt full {}", BATCH_LOCALS_FRAMES),
+            "--args",
+            executable,
+        ]);
+    }
+
+    cmd
+}
+
+// replay `crash_filename` with `debugger_batch_command`, saving its output
+// (debugger and target, interleaved) to `output_path` before printing it
+fn debugger_batch<T>(
+    executable: &str,
+    crash_filename: &Path,
+    args: T,
+    output_path: &Path,
+) -> process::ExitStatus
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    if let Some(parent) = output_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let output = fs::File::create(output_path).unwrap_or_else(|e| {
+        eprintln!(
+            "error: failed to create \"{}\": {}",
+            output_path.display(),
+            e
+        );
+        process::exit(1);
+    });
+
+    let status = debugger_batch_command(executable)
+        .args(args)
+        .envs(sanitizer_options())
+        .env("CARGO_HONGGFUZZ_CRASH_FILENAME", crash_filename)
+        .env(
+            "RUST_BACKTRACE",
+            env::var("RUST_BACKTRACE").unwrap_or_else(|_| "1".into()),
+        )
+        .stdin(Stdio::null())
+        .stdout(output.try_clone().unwrap())
+        .stderr(output)
+        .status()
+        .unwrap_or_else(|e| {
+            eprintln!("cannot execute {}: {:?}", debugger_name(), e);
+            process::exit(1);
+        });
+
+    let _ = std::io::stdout().write_all(&fs::read(output_path).unwrap_or_default());
+    println!("debugger output saved to \"{}\"", output_path.display());
+    status
+}
+
+// `rr` binary to record and replay crashes with, from `HFUZZ_DEBUGGER=rr` or `--record`
+fn rr_debugger(record: bool) -> Option<String> {
+    match env::var("HFUZZ_DEBUGGER") {
+        Ok(debugger) if Path::new(&debugger).file_name().is_some_and(|f| f == "rr") => {
+            Some(debugger)
+        }
+        _ if record => Some("rr".into()),
+        _ => None,
+    }
+}
+
+// record the replay of `crash_filename` with `rr record`, then open the
+// recording in `rr replay` stopped on the panic so that one can step backwards
+fn rr_record_replay<T>(
+    rr: &str,
+    executable: &str,
+    trace_dir: &Path,
+    crash_filename: &Path,
+    args: T,
+) -> process::ExitStatus
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    // `rr record -o` wants a directory that doesn't exist yet
+    let _ = fs::remove_dir_all(trace_dir);
+    if let Some(parent) = trace_dir.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    let status = Command::new(rr)
+        .arg("record")
+        .arg("-o")
+        .arg(trace_dir)
+        .arg(executable)
+        .args(args)
+        .envs(sanitizer_options())
+        .env("CARGO_HONGGFUZZ_CRASH_FILENAME", crash_filename)
+        .env(
+            "RUST_BACKTRACE",
+            env::var("RUST_BACKTRACE").unwrap_or_else(|_| "1".into()),
+        )
+        .status()
+        .unwrap_or_else(|e| {
+            eprintln!("cannot execute {}: {:?}", rr, e);
+            process::exit(1);
+        });
+    // the recorded run is expected to crash, only a missing trace is an error
+    if !trace_dir.is_dir() {
+        eprintln!("{} record failed ({})", rr, status);
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    Command::new(rr)
+        .arg("replay")
+        .arg(trace_dir)
+        .args(["--", "-ex", "b rust_panic", "-ex", "c", "-ex", "bt"])
+        .status()
+        .unwrap()
+}
+
+// `HFUZZ_WORKSPACE` takes precedence over the `workspace` setting
+fn workspace_dir(config: &TargetConfig) -> String {
+    env::var("HFUZZ_WORKSPACE").unwrap_or_else(|_| {
+        config
+            .workspace
+            .as_ref()
+            .map_or_else(|| HONGGFUZZ_WORKSPACE.into(), |w| w.display().to_string())
+    })
+}
+
+// `HFUZZ_INPUT` takes precedence over the `input` setting
+fn input_dir(target: &str, config: &TargetConfig) -> String {
+    env::var("HFUZZ_INPUT").unwrap_or_else(|_| match &config.input {
+        Some(input) => input.display().to_string(),
+        None => format!("{}/{}/input", workspace_dir(config), target),
+    })
+}
+
+// get user-defined args for building, `HFUZZ_BUILD_ARGS` comes last to override the `build_args` setting
+fn build_args(config: &TargetConfig) -> Vec<String> {
+    let hfuzz_build_args = env::var("HFUZZ_BUILD_ARGS").unwrap_or_default();
+    // FIXME: we split by whitespace without respecting escaping or quotes
+    config
+        .build_args
+        .iter()
+        .cloned()
+        .chain(hfuzz_build_args.split_whitespace().map(String::from))
+        .collect()
+}
+
+// take `--profile NAME` or `--profile=NAME` out of cargo build arguments
+fn split_profile(args: Vec<String>) -> (Option<String>, Vec<String>) {
+    let mut profile = None;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match option_value(&arg, "--profile", &mut args) {
+            Some(name) => profile = Some(name),
+            None => rest.push(arg),
+        }
+    }
+    (profile, rest)
+}
+
+// add some flags to sanitizers to make them work with Rust code, user provided options come last to override them
+fn sanitizer_options() -> Vec<(&'static str, String)> {
+    campaign::SANITIZER_OPTIONS
+        .iter()
+        .map(|&(var, defaults)| {
+            (var, format!("{}:{}", defaults, env::var(var).unwrap_or_default()))
+        })
+        .collect()
+}
+
+fn hfuzz_version() {
+    println!("cargo-hfuzz {}", VERSION);
+
+    let honggfuzz_target = BuildOptions::default().target_dir();
+    if !Path::new(&honggfuzz_target).join("honggfuzz").exists() {
+        return;
+    }
+    if minimal_driver(&honggfuzz_target) {
+        println!("honggfuzz driver: minimal, built without libbfd and libunwind (`minimal-driver` feature)");
+        println!("- crashes are told apart by their program counter only, so `cargo hfuzz run` saves all of them (`--save_all`)");
+        println!("- their stack traces come from replaying them with `cargo hfuzz triage` or `cargo hfuzz run-debug`");
+    } else {
+        println!("honggfuzz driver: full");
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Health {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Result of one of the checks of `cargo hfuzz doctor`.
+struct Diagnostic {
+    health: Health,
+    message: String,
+    /// how to fix a warning or an error
+    fix: Option<String>,
+}
+
+impl Diagnostic {
+    fn ok(message: String) -> Diagnostic {
+        Diagnostic {
+            health: Health::Ok,
+            message,
+            fix: None,
+        }
+    }
+
+    fn warning(message: String, fix: &str) -> Diagnostic {
+        Diagnostic {
+            health: Health::Warning,
+            message,
+            fix: Some(fix.to_string()),
+        }
+    }
+
+    fn error(message: String, fix: &str) -> Diagnostic {
+        Diagnostic {
+            health: Health::Error,
+            message,
+            fix: Some(fix.to_string()),
+        }
+    }
+}
+
+// development headers needed to build the `honggfuzz` command on Linux, and their Debian/Ubuntu packages
+const DRIVER_HEADERS: &[(&str, &str)] = &[
+    ("bfd.h", "binutils-dev"),
+    ("libunwind-ptrace.h", "libunwind-dev"),
+    ("lzma.h", "liblzma-dev"),
+];
+
+fn hfuzz_doctor(crate_root: Option<&Path>) {
+    let mut diagnostics = Vec::new();
+
+    // C compiler, for the honggfuzz runtime
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".into());
+    let cc = cc.split_whitespace().next().unwrap_or("cc").to_string();
+    diagnostics.push(match command_output(&cc, &["--version"]) {
+        Some(version) => Diagnostic::ok(format!(
+            "C compiler: {}",
+            version.lines().next().unwrap_or(&cc)
+        )),
+        None => Diagnostic::error(
+            format!("C compiler: \"{}\" not found", cc),
+            "install a C compiler, e.g. `sudo apt install build-essential`, or set CC",
+        ),
+    });
+
+    // GNU make, for the honggfuzz command
+    let make = if cfg!(any(
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )) {
+        "gmake"
+    } else {
+        "make"
+    };
+    diagnostics.push(match command_output(make, &["--version"]) {
+        Some(version) if version.contains("GNU Make") => {
+            Diagnostic::ok(format!("make: {}", version.lines().next().unwrap_or(make)))
+        }
+        Some(_) => Diagnostic::error(
+            format!("make: \"{}\" is not GNU make", make),
+            "install GNU make",
+        ),
+        None => Diagnostic::error(
+            format!("make: \"{}\" not found", make),
+            "install GNU make, e.g. `sudo apt install make`",
+        ),
+    });
+
+    // system libraries of the honggfuzz command
+    if env::var_os("HFUZZ_HONGGFUZZ_DIR").is_some() {
+        diagnostics.push(Diagnostic::ok(
+            "libbfd, libunwind and liblzma headers: not needed, HFUZZ_HONGGFUZZ_DIR is set".into(),
+        ));
+    } else if cfg!(target_os = "linux") {
+        for (header, package) in DRIVER_HEADERS {
+            diagnostics.push(if header_exists(&cc, header) {
+                Diagnostic::ok(format!("header <{}>", header))
+            } else {
+                Diagnostic::error(
+                    format!("header <{}> not found", header),
+                    &format!(
+                        "install it, e.g. `sudo apt install {}`, or use the `minimal-driver` feature of honggfuzz",
+                        package
+                    ),
+                )
+            });
+        }
+    }
+
+    // nightly toolchain, for sanitizers
+    let nightly = rustc_version::version_meta()
+        .is_ok_and(|v| matches!(v.channel, Channel::Nightly | Channel::Dev));
+    diagnostics.push(if nightly {
+        Diagnostic::ok("nightly toolchain: in use".into())
+    } else if command_output("rustup", &["run", "nightly", "rustc", "--version"]).is_some() {
+        Diagnostic::ok(
+            "nightly toolchain: installed, use `cargo +nightly hfuzz ...` for sanitizers".into(),
+        )
+    } else {
+        Diagnostic::warning(
+            "nightly toolchain: not installed, it is needed by `--sanitizer`".into(),
+            "rustup toolchain install nightly --component rust-src",
+        )
+    });
+
+    // debugger, for `cargo hfuzz run-debug`
+    let debugger = debugger_name();
+    diagnostics.push(if program_exists(&debugger) {
+        Diagnostic::ok(format!("debugger: {}", debugger))
+    } else if env::var_os("HFUZZ_DEBUGGER").is_none() && program_exists("rust-gdb") {
+        Diagnostic::warning(
+            format!("debugger: \"{}\" not found, but rust-gdb is", debugger),
+            "export HFUZZ_DEBUGGER=rust-gdb",
+        )
+    } else {
+        Diagnostic::warning(
+            format!(
+                "debugger: \"{}\" not found, it is needed by `cargo hfuzz run-debug`",
+                debugger
+            ),
+            "install lldb or gdb, e.g. `sudo apt install lldb`, and set HFUZZ_DEBUGGER if needed",
+        )
+    });
+
+    // hardware-based feedback of `cargo hfuzz run-no-instr`
+    if let Some(paranoid) = fs::read_to_string("/proc/sys/kernel/perf_event_paranoid")
+        .ok()
+        .and_then(|value| value.trim().parse::<i32>().ok())
+    {
+        diagnostics.push(if paranoid <= 1 {
+            Diagnostic::ok(format!("perf_event_paranoid: {}", paranoid))
+        } else {
+            Diagnostic::warning(
+                format!(
+                    "perf_event_paranoid: {}, the `--linux_perf_*` feedback of `cargo hfuzz run-no-instr` will not work",
+                    paranoid
+                ),
+                "sudo sysctl kernel.perf_event_paranoid=1",
+            )
+        });
+    }
+
+    // crashes handed to a program are slow to be reported to honggfuzz
+    if let Ok(core_pattern) = fs::read_to_string("/proc/sys/kernel/core_pattern") {
+        let core_pattern = core_pattern.trim();
+        diagnostics.push(if core_pattern.starts_with('|') {
+            Diagnostic::warning(
+                format!(
+                    "core_pattern: crashes are piped to \"{}\", which slows down fuzzing",
+                    core_pattern
+                ),
+                "echo core | sudo tee /proc/sys/kernel/core_pattern",
+            )
+        } else {
+            Diagnostic::ok(format!("core_pattern: {}", core_pattern))
+        });
+    }
+
+    // cargo-hfuzz and the honggfuzz dependency, checked again by build.rs
+    if let Some(lock_file) = crate_root.and_then(find_lock_file) {
+        let versions = locked_versions(&lock_file, "honggfuzz");
+        diagnostics.push(if versions.is_empty() {
+            Diagnostic::warning(
+                format!("honggfuzz dependency: not in \"{}\"", lock_file.display()),
+                "cargo add honggfuzz",
+            )
+        } else if versions.iter().any(|version| version == VERSION) {
+            Diagnostic::ok(format!("honggfuzz dependency: {}, same as cargo-hfuzz", VERSION))
+        } else {
+            Diagnostic::error(
+                format!(
+                    "honggfuzz dependency: {} in Cargo.lock, but cargo-hfuzz is {}",
+                    versions.join(", "),
+                    VERSION
+                ),
+                &format!(
+                    "cargo install honggfuzz --version {}, or change the dependency to `honggfuzz = \"={}\"`",
+                    versions[0], VERSION
+                ),
+            )
+        });
+    }
+
+    for diagnostic in &diagnostics {
+        let label = match diagnostic.health {
+            Health::Ok => "ok",
+            Health::Warning => "warning",
+            Health::Error => "error",
+        };
+        println!("{:<8}{}", label, diagnostic.message);
+        if let Some(fix) = &diagnostic.fix {
+            println!("{:<8}fix: {}", "", fix);
+        }
+    }
+    if diagnostics.iter().any(|d| d.health == Health::Error) {
+        process::exit(1);
+    }
+}
+
+// whether the C compiler `cc` finds `header`
+fn header_exists(cc: &str, header: &str) -> bool {
+    let child = Command::new(cc)
+        .args(["-E", "-x", "c", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return false;
+    };
+    let include = format!("#include <{}>\n", header);
+    let written = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(include.as_bytes())
+        .is_ok();
+    child.wait().is_ok_and(|status| status.success()) && written
+}
+
+// whether `program` is a path to a file or is found in `PATH`
+fn program_exists(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+// `Cargo.lock` of the crate, or of the workspace it belongs to
+fn find_lock_file(crate_root: &Path) -> Option<PathBuf> {
+    crate_root
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())
+}
+
+// versions of the package `name` in `lock_file`
+fn locked_versions(lock_file: &Path, name: &str) -> Vec<String> {
+    let lock: toml::Table = fs::read_to_string(lock_file)
+        .ok()
+        .and_then(|lock| lock.parse().ok())
+        .unwrap_or_default();
+    lock.get("package")
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|package| package.get("name").and_then(toml::Value::as_str) == Some(name))
+        .filter_map(|package| package.get("version").and_then(toml::Value::as_str))
+        .map(String::from)
+        .collect()
+}
+
+// whether the `honggfuzz` command of `honggfuzz_target` was built without stack unwinding
+fn minimal_driver(honggfuzz_target: &str) -> bool {
+    Path::new(honggfuzz_target)
+        .join(MINIMAL_DRIVER_FILENAME)
+        .exists()
+}
+
+fn hfuzz_run<T>(args: T, crate_root: &Path, build_type: &BuildType, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let mut args = args.peekable();
+
+    // options of `cargo hfuzz run` come before the target name, everything after it is given to the target
+    let mut stats_file = false;
+    let mut record = false;
+    let mut batch = false;
+    let mut batch_output = None;
+    let mut supervise = false;
+    let mut on_crash = Vec::new();
+    let mut options = BuildOptions::default();
+    let mut fuzz_target = None;
+    while let Some(arg) = args.next_if(|arg| arg.starts_with("--")) {
+        if let Some(selected) = FuzzTarget::parse_arg(&arg, &mut args) {
+            fuzz_target = Some(selected);
+            continue;
+        }
+        match arg.as_str() {
+            "--stats-file" => stats_file = true,
+            "--record" if *build_type == BuildType::Debug => record = true,
+            "--batch" if *build_type == BuildType::Debug => batch = true,
+            _ if *build_type == BuildType::Debug && arg.starts_with("--batch-output") => {
+                batch_output = option_value(&arg, "--batch-output", &mut args).map(PathBuf::from);
+                batch = true;
+            }
+            "--supervise" if *build_type != BuildType::Debug => supervise = true,
+            _ if *build_type != BuildType::Debug && arg.starts_with("--on-crash-exec") => {
+                if let Some(command) = option_value(&arg, "--on-crash-exec", &mut args) {
+                    on_crash.push(CrashAction::Exec(command));
+                }
+                supervise = true;
+            }
+            _ if *build_type != BuildType::Debug && arg.starts_with("--on-crash") => {
+                let name = option_value(&arg, "--on-crash", &mut args).unwrap_or_default();
+                on_crash.push(CrashAction::from_name(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "unknown action \"{}\" for --on-crash, possible values are: report, triage",
+                        name
+                    );
+                    process::exit(1);
+                }));
+                supervise = true;
+            }
+            _ if options.parse_arg(&arg, &mut args) => {}
+            _ => {
+                eprintln!("unknown option \"{}\"", arg);
+                process::exit(1);
+            }
+        }
+    }
+
+    // a binary unless `--example NAME`, `--test NAME` ... selected another kind of target
+    let fuzz_target = fuzz_target.unwrap_or_else(|| {
+        FuzzTarget::bin(args.next().unwrap_or_else(||{
+            eprintln!("please specify the name of the target like this \"cargo hfuzz run[-debug|-no-instr] [--stats-file] [--supervise] [--on-crash ACTION] [--on-crash-exec COMMAND] [--record] [--batch] [--batch-output FILE] [--target TRIPLE] [--sanitizer NAME] [--profile NAME] TARGET|--example NAME|--test NAME|--bench NAME [ ARGS ... ]\"");
+            process::exit(1);
+        }))
+    });
+    let target = fuzz_target.name.clone();
+
+    let config = config.target(&target);
+
+    let artifacts = hfuzz_build(
+        fuzz_target.build_args().into_iter(),
+        crate_root,
+        build_type,
+        &config,
+        &options,
+    );
+
+    match *build_type {
+        BuildType::Debug => {
+            let crash_filename = args.next().unwrap_or_else(||{
+                eprintln!("please specify the crash filename like this \"cargo hfuzz run-debug TARGET CRASH_FILENAME [ ARGS ... ]\"");
+                process::exit(1);
+            });
+
+            let executable = fuzz_target.executable(&artifacts).display().to_string();
+
+            if batch {
+                if rr_debugger(record).is_some() {
+                    eprintln!("the batch mode can't be used to record with rr");
+                    process::exit(1);
+                }
+                let output_path = batch_output
+                    .unwrap_or_else(|| PathBuf::from(format!("{}.backtrace.txt", crash_filename)));
+                let status =
+                    debugger_batch(&executable, Path::new(&crash_filename), args, &output_path);
+                if !status.success() {
+                    process::exit(status.code().unwrap_or(1));
+                }
+                return;
+            }
+
+            if let Some(rr) = rr_debugger(record) {
+                let trace_dir = Path::new(&workspace_dir(&config))
+                    .join(&target)
+                    .join("rr-trace");
+                let status = rr_record_replay(
+                    &rr,
+                    &executable,
+                    &trace_dir,
+                    Path::new(&crash_filename),
+                    args,
+                );
+                if !status.success() {
+                    process::exit(status.code().unwrap_or(1));
+                }
+                return;
+            }
+
+            let status = debugger_command(&executable)
+                .args(args)
+                .envs(sanitizer_options())
+                .env("CARGO_HONGGFUZZ_CRASH_FILENAME", crash_filename)
+                .env(
+                    "RUST_BACKTRACE",
+                    env::var("RUST_BACKTRACE").unwrap_or_else(|_| "1".into()),
+                )
+                .status()
+                .unwrap();
+            if !status.success() {
+                process::exit(status.code().unwrap_or(1));
+            }
+        }
+        _ if supervise => {
+            hfuzz_supervise(
+                &fuzz_target,
+                &artifacts,
+                crate_root,
+                &config,
+                &options,
+                &on_crash,
+                args.collect(),
+            );
+        }
+        _ => {
+            let mut command =
+                honggfuzz_campaign(&fuzz_target, &artifacts, &config, &options, stats_file, &[])
+                    .command();
+            let err = command // exec honggfuzz replacing current process
+                .args(args)
+                .exec();
+
+            // code flow will only reach here if honggfuzz failed to execute
+            eprintln!(
+                "cannot execute {}, try to execute \"cargo hfuzz build\" from fuzzed project directory",
+                command.get_program().to_string_lossy()
+            );
+            eprintln!("{:?}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// What `cargo hfuzz run --supervise` does with the crashes found by honggfuzz.
+enum CrashAction {
+    /// `--on-crash report`: print what honggfuzz reported about them
+    Report,
+    /// `--on-crash triage`: replay them in the debug build and group them
+    Triage,
+    /// `--on-crash-exec COMMAND`: run a shell command for each of them
+    Exec(String),
+}
+
+impl CrashAction {
+    fn from_name(name: &str) -> Option<CrashAction> {
+        match name {
+            "report" => Some(CrashAction::Report),
+            "triage" => Some(CrashAction::Triage),
+            _ => None,
+        }
+    }
+}
+
+// pid of the honggfuzz child of `cargo hfuzz run --supervise`
+static SUPERVISED_PID: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn forward_signal(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _context: *mut libc::c_void,
+) {
+    // signals of the terminal (Ctrl-C ...) already reach honggfuzz, in the same process group,
+    // only the ones sent to cargo-hfuzz with kill(2) are forwarded
+    let sent_by_user = unsafe { (*info).si_code } <= 0;
+    let pid = SUPERVISED_PID.load(Ordering::SeqCst);
+    if sent_by_user && pid > 0 {
+        unsafe {
+            libc::kill(pid, signal);
+        }
+    }
+}
+
+fn forward_signals(pid: u32) {
+    SUPERVISED_PID.store(pid as i32, Ordering::SeqCst);
+    let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+        forward_signal;
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+// run honggfuzz as a child instead of replacing cargo-hfuzz with it, then summarize the
+// campaign, run the `on_crash` actions and exit with 1 if new crashes were found
+fn hfuzz_supervise(
+    fuzz_target: &FuzzTarget,
+    artifacts: &[Artifact],
+    crate_root: &Path,
+    config: &TargetConfig,
+    options: &BuildOptions,
+    on_crash: &[CrashAction],
+    target_args: Vec<String>,
+) -> ! {
+    let target = &fuzz_target.name;
+    // the statistics tell the coverage reached
+    let campaign = honggfuzz_campaign(fuzz_target, artifacts, config, options, true, &[])
+        .target_args(target_args);
+    let input = PathBuf::from(input_dir(target, config));
+    let crashes_before = campaign.crashes();
+    let corpus_before = count_files(&input);
+    let stats_before = campaign.stats().ok().flatten();
+
+    let status = campaign.spawn().and_then(|mut child| {
+        forward_signals(child.id());
+        child.wait()
+    });
+    SUPERVISED_PID.store(0, Ordering::SeqCst);
+    let status = status.unwrap_or_else(|e| {
+        eprintln!(
+            "cannot execute {}, try to execute \"cargo hfuzz build\" from fuzzed project directory",
+            campaign.command().get_program().to_string_lossy()
+        );
+        eprintln!("{:?}", e);
+        process::exit(2);
+    });
+
+    let new_crashes: Vec<_> = campaign
+        .crashes()
+        .into_iter()
+        .filter(|crash| !crashes_before.contains(crash))
+        .collect();
+    let corpus_after = count_files(&input);
+
+    println!();
+    match (status.code(), status.signal()) {
+        (Some(code), _) => println!("honggfuzz exited with status {}", code),
+        (None, Some(signal)) => println!("honggfuzz was killed by signal {}", signal),
+        _ => {}
+    }
+    println!(
+        "corpus: {} inputs ({:+})",
+        corpus_after,
+        corpus_after as i64 - corpus_before as i64
+    );
+    if let Some(stats) = campaign.stats().ok().flatten() {
+        let (edges_before, blocks_before) =
+            stats_before.map_or((0, 0), |before| (before.edge_cov, before.block_cov));
+        println!(
+            "coverage: {} edges ({:+}), {} blocks ({:+})",
+            stats.edge_cov,
+            stats.edge_cov as i64 - edges_before as i64,
+            stats.block_cov,
+            stats.block_cov as i64 - blocks_before as i64
+        );
+    }
+    println!("new crashes: {}", new_crashes.len());
+    for crash in &new_crashes {
+        println!("  {}", crash.display());
+    }
+
+    if !new_crashes.is_empty() {
+        for action in on_crash {
+            match action {
+                CrashAction::Report => print_crash_reports(target, config, &new_crashes),
+                CrashAction::Triage => triage_crashes(
+                    fuzz_target,
+                    new_crashes.clone(),
+                    triage::DEFAULT_FRAMES,
+                    false,
+                    crate_root,
+                    config,
+                    options,
+                ),
+                CrashAction::Exec(shell_command) => {
+                    for crash in &new_crashes {
+                        let status = Command::new("sh")
+                            .args(["-c", shell_command])
+                            .env("CARGO_HONGGFUZZ_CRASH_FILENAME", crash)
+                            .status();
+                        if !status.is_ok_and(|status| status.success()) {
+                            eprintln!("\"{}\" failed for {}", shell_command, crash.display());
+                        }
+                    }
+                }
+            }
+        }
+        process::exit(1);
+    }
+    process::exit(if status.success() { 0 } else { 2 });
+}
+
+// what honggfuzz wrote in its report about `crashes`
+fn print_crash_reports(target: &str, config: &TargetConfig, crashes: &[PathBuf]) {
+    let report_path = Path::new(&workspace_dir(config))
+        .join(target)
+        .join(report::REPORT_FILENAME);
+    let reports = report::read(&report_path).unwrap_or_else(|e| {
+        eprintln!("error: failed to read \"{}\": {}", report_path.display(), e);
+        Vec::new()
+    });
+    for crash in crashes {
+        let file_name = crash.file_name();
+        let Some(report) = reports.iter().find(|report| {
+            report
+                .crash_file
+                .as_deref()
+                .is_some_and(|path| Path::new(path).file_name() == file_name)
+        }) else {
+            continue;
+        };
+        println!();
+        println!("{}", crash.display());
+        if let Some(signal) = &report.signal {
+            println!("  signal: {}", signal);
+        }
+        if let Some(description) = &report.description {
+            println!("  description: {}", description);
+        }
+        for frame in &report.backtrace {
+            let location = match (&frame.file, frame.line) {
+                (Some(file), Some(line)) => format!(" ({}:{})", file, line),
+                _ => String::new(),
+            };
+            println!(
+                "  at {:#x} {}{}",
+                frame.pc,
+                frame.function.as_deref().unwrap_or("??"),
+                location
+            );
+        }
+    }
+}
+
+// honggfuzz campaign fuzzing `fuzz_target`, already built into `artifacts`, arguments for the
+// target itself can still be appended to its command
+fn honggfuzz_campaign(
+    fuzz_target: &FuzzTarget,
+    artifacts: &[Artifact],
+    config: &TargetConfig,
+    options: &BuildOptions,
+    stats_file: bool,
+    extra_args: &[String],
+) -> Campaign {
+    let target = &fuzz_target.name;
+    let honggfuzz_target = options.target_dir();
+    let honggfuzz_workspace = workspace_dir(config);
+    let honggfuzz_input = input_dir(target, config);
+
+    // get user-defined args for honggfuzz, `HFUZZ_RUN_ARGS` comes last to override the settings
+    let hfuzz_run_args = env::var("HFUZZ_RUN_ARGS").unwrap_or_default();
+    // FIXME: we split by whitespace without respecting escaping or quotes
+    let hfuzz_run_args = hfuzz_run_args.split_whitespace();
+
+    fs::create_dir_all(format!("{}/{}/input", &honggfuzz_workspace, target))
+        .unwrap_or_else(|_| {
+            println!(
+                "error: failed to create \"{}/{}/input\"",
+                &honggfuzz_workspace, target
+            );
+        });
+
+    let workspace = Path::new(&honggfuzz_workspace).join(target);
+    let mut campaign = Campaign::new(
+        format!("{}/honggfuzz", &honggfuzz_target),
+        fuzz_target.executable(artifacts),
+    )
+    .workspace(&workspace)
+    .input(&honggfuzz_input)
+    // every panic aborts at the same program counter, without their stack honggfuzz
+    // would keep only one of them, `cargo hfuzz triage` tells them apart instead
+    .save_all(minimal_driver(&honggfuzz_target));
+    if stats_file {
+        // read back by `cargo hfuzz stats`
+        campaign = campaign.stats_file(workspace.join(stats::STATS_FILENAME));
+    }
+    if let Some(timeout) = config.timeout {
+        campaign = campaign.timeout(Duration::from_secs(timeout));
+    }
+    if let Some(threads) = config.threads {
+        campaign = campaign.threads(threads);
+    }
+    if let Some(dictionary) = &config.dictionary {
+        campaign = campaign.dictionary(dictionary);
+    }
+    for (var, _) in campaign::SANITIZER_OPTIONS {
+        campaign = campaign.sanitizer_options(var, &env::var(var).unwrap_or_default());
+    }
+    campaign
+        .args(extra_args)
+        .args(&config.run_args)
+        .args(hfuzz_run_args) // allows user-specified arguments to be given to honggfuzz
+}
+
+fn count_files(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.as_ref().is_ok_and(|entry| entry.path().is_file()))
+        .count()
+}
+
+/// Outcome of fuzzing a target for a limited time.
+struct FuzzSummary {
+    target: FuzzTarget,
+    /// whether honggfuzz ran and exited successfully
+    success: bool,
+    /// corpus size before and after fuzzing
+    corpus: (usize, usize),
+    /// number of crash files saved after fuzzing
+    crashes: usize,
+    /// crash files saved while fuzzing
+    new_crashes: Vec<PathBuf>,
+}
+
+// fuzz `targets`, already built into `artifacts`, `jobs` of them at the same time, for
+// `run_time` seconds each
+fn fuzz_targets(
+    targets: &[FuzzTarget],
+    artifacts: &[Artifact],
+    config: &Config,
+    options: &BuildOptions,
+    run_time: u64,
+    jobs: usize,
+    extra_args: &[String],
+) -> Vec<FuzzSummary> {
+    // share the cores between the targets fuzzed at the same time
+    let cores = thread::available_parallelism().map_or(1, std::num::NonZero::get);
+    let threads = (cores / jobs).max(1);
+
+    let mut summaries = Vec::new();
+    for batch in targets.chunks(jobs) {
+        let runs: Vec<_> = batch
+            .iter()
+            .map(|fuzz_target| {
+                let target = &fuzz_target.name;
+                let target_config = config.target(target);
+                let workspace = Path::new(&workspace_dir(&target_config)).join(target);
+                let input = PathBuf::from(input_dir(target, &target_config));
+                let mut campaign = honggfuzz_campaign(
+                    fuzz_target,
+                    artifacts,
+                    &target_config,
+                    options,
+                    false,
+                    extra_args,
+                )
+                .run_time(Duration::from_secs(run_time));
+                // the `threads` setting of the target takes precedence
+                if target_config.threads.is_none() {
+                    campaign = campaign.threads(threads as u64);
+                }
+                let mut command = campaign.command();
+
+                // honggfuzz output of concurrent runs would be mixed up on the terminal
+                let log_path = workspace.join("HONGGFUZZ.RUN-ALL.LOG");
+                let log = fs::File::create(&log_path).unwrap_or_else(|e| {
+                    eprintln!("error: failed to create \"{}\": {}", log_path.display(), e);
+                    process::exit(1);
+                });
+                eprintln!(
+                    "fuzzing {} for {} ({} threads), logs in {}",
+                    target,
+                    format_duration(Duration::from_secs(run_time)),
+                    threads,
+                    log_path.display()
+                );
+
+                let corpus = count_files(&input);
+                let crashes = campaign.crashes();
+                let child = command
+                    .stdin(Stdio::null())
+                    .stdout(log.try_clone().unwrap())
+                    .stderr(log)
+                    .spawn();
+                (fuzz_target, campaign, input, corpus, crashes, child)
+            })
+            .collect();
+
+        for (fuzz_target, campaign, input, corpus, crashes, child) in runs {
+            let success = match child.and_then(|mut child| child.wait()) {
+                Ok(status) => status.success(),
+                Err(e) => {
+                    eprintln!("cannot execute honggfuzz for {}: {:?}", fuzz_target.name, e);
+                    false
+                }
+            };
+            let crashes_after = campaign.crashes();
+            summaries.push(FuzzSummary {
+                target: fuzz_target.clone(),
+                success,
+                corpus: (corpus, count_files(&input)),
+                crashes: crashes_after.len(),
+                new_crashes: crashes_after
+                    .into_iter()
+                    .filter(|crash| !crashes.contains(crash))
+                    .collect(),
+            });
+        }
+    }
+    summaries
+}
+
+// build `targets` with their own settings, the ones sharing the same build
+// settings together in a single `cargo build`, and return the executables
+fn build_targets(
+    targets: &[FuzzTarget],
+    crate_root: &Path,
+    build_type: &BuildType,
+    config: &Config,
+    options: &BuildOptions,
+) -> Vec<Artifact> {
+    let mut groups: Vec<(TargetConfig, Vec<&FuzzTarget>)> = Vec::new();
+    for target in targets {
+        let settings = config.target(&target.name).build_settings();
+        match groups.iter_mut().find(|(other, _)| *other == settings) {
+            Some((_, group)) => group.push(target),
+            None => groups.push((settings, vec![target])),
+        }
+    }
+    let mut artifacts = Vec::new();
+    for (_, group) in groups {
+        artifacts.extend(hfuzz_build(
+            group.iter().flat_map(|target| target.build_args()),
+            crate_root,
+            build_type,
+            &config.target(&group[0].name),
+            options,
+        ));
+    }
+    artifacts
+}
+
+fn hfuzz_run_all<T>(mut args: T, crate_root: &Path, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let mut run_time = 600;
+    let mut jobs = 1;
+    let mut options = BuildOptions::default();
+    let mut targets = Vec::new();
+    while let Some(arg) = args.next() {
+        if options.parse_arg(&arg, &mut args) {
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--time", &mut args) {
+            run_time = value.parse().unwrap_or_else(|_| {
+                eprintln!("--time expects a number of seconds (eg. --time 600)");
+                process::exit(1);
+            });
+        } else if let Some(value) = option_value(&arg, "--jobs", &mut args) {
+            jobs = value.parse().ok().filter(|jobs| *jobs > 0).unwrap_or_else(|| {
+                eprintln!("--jobs expects a number of targets to fuzz at the same time (eg. --jobs 4)");
+                process::exit(1);
+            });
+        } else if let Some(fuzz_target) = FuzzTarget::parse_arg(&arg, &mut args) {
+            targets.push(fuzz_target);
+        } else if arg.starts_with("--") {
+            eprintln!("unknown option \"{}\"", arg);
+            process::exit(1);
+        } else {
+            targets.push(FuzzTarget::bin(arg));
+        }
+    }
+
+    if targets.is_empty() {
+        targets = bin_targets(crate_root)
+            .into_iter()
+            .map(FuzzTarget::bin)
+            .collect();
+    }
+    if targets.is_empty() {
+        eprintln!("no binary target found, please specify them like this \"cargo hfuzz run-all [--time SECONDS] [--jobs N] [ TARGET|--example NAME|--test NAME|--bench NAME ... ]\"");
+        process::exit(1);
+    }
+
+    let artifacts = build_targets(
+        &targets,
+        crate_root,
+        &BuildType::ReleaseInstrumented,
+        config,
+        &options,
+    );
+
+    let summaries = fuzz_targets(&targets, &artifacts, config, &options, run_time, jobs, &[]);
+
+    println!(
+        "{:<30}  {:>10}  {:>8}  {:>11}  {:>8}  STATUS",
+        "TARGET", "NEW INPUTS", "CORPUS", "NEW CRASHES", "CRASHES"
+    );
+    for summary in &summaries {
+        println!(
+            "{:<30}  {:>10}  {:>8}  {:>11}  {:>8}  {}",
+            summary.target.name,
+            summary.corpus.1.saturating_sub(summary.corpus.0),
+            summary.corpus.1,
+            summary.new_crashes.len(),
+            summary.crashes,
+            if summary.success { "ok" } else { "failed" }
+        );
+    }
+
+    if summaries.iter().any(|summary| !summary.success) {
+        process::exit(1);
+    }
+}
+
+// command running a target built with `BuildType::Debug` on `path`
+fn replay_command(executable: &str, path: &Path) -> Command {
+    let mut command = Command::new(executable);
+    command
+        .envs(sanitizer_options())
+        .env("CARGO_HONGGFUZZ_CRASH_FILENAME", path)
+        .env("RUST_BACKTRACE", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    command
+}
+
+// timeout of a replay: the `timeout` setting, or a default leaving room for the
+// slower debug build, as honggfuzz's own default is one second
+fn replay_timeout(config: &TargetConfig) -> Duration {
+    Duration::from_secs(config.timeout.unwrap_or(DEFAULT_REPLAY_TIMEOUT))
+}
+
+// run a target built with `BuildType::Debug` on `path`, killing it after `timeout`,
+// and return its outcome with its panic output
+fn replay_output(executable: &str, path: &Path, timeout: Duration) -> (Outcome, Vec<u8>) {
+    let mut child = replay_command(executable, path)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| {
+            eprintln!("cannot execute {}: {:?}", executable, e);
+            process::exit(1);
+        });
+
+    // read stderr while waiting, the target would block on a full pipe
+    let mut stderr = child.stderr.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stderr.read_to_end(&mut output);
+        output
+    });
+
+    let deadline = Instant::now() + timeout;
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            timed_out = true;
+            break child.wait().unwrap();
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let stderr = reader.join().unwrap_or_default();
+
+    let outcome = if timed_out {
+        Outcome::Timeout
+    } else {
+        Outcome::from_status(status.code(), status.signal())
+    };
+    (outcome, stderr)
+}
+
+// replay `path` with a target built with `BuildType::Debug`, killing it after `timeout`
+fn replay(executable: &str, path: PathBuf, timeout: Duration) -> Crash {
+    eprintln!("replaying {}", path.display());
+    let (outcome, stderr) = replay_output(executable, &path, timeout);
+    Crash {
+        size: fs::metadata(&path).map(|m| m.len()).unwrap_or_default(),
+        path,
+        outcome,
+        panic: PanicInfo::parse(&String::from_utf8_lossy(&stderr)),
+    }
+}
+
+fn hfuzz_ci<T>(mut args: T, crate_root: &Path, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let mut run_time = 60;
+    let mut jobs = 1;
+    let mut junit_path = None;
+    let mut sarif_path = None;
+    let mut options = BuildOptions::default();
+    let mut targets = Vec::new();
+    while let Some(arg) = args.next() {
+        if options.parse_arg(&arg, &mut args) {
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--time", &mut args) {
+            run_time = value.parse().unwrap_or_else(|_| {
+                eprintln!("--time expects a number of seconds (eg. --time 60)");
+                process::exit(1);
+            });
+        } else if let Some(value) = option_value(&arg, "--jobs", &mut args) {
+            jobs = value.parse().ok().filter(|jobs| *jobs > 0).unwrap_or_else(|| {
+                eprintln!("--jobs expects a number of targets to fuzz at the same time (eg. --jobs 4)");
+                process::exit(1);
+            });
+        } else if let Some(value) = option_value(&arg, "--junit", &mut args) {
+            junit_path = Some(value);
+        } else if let Some(value) = option_value(&arg, "--sarif", &mut args) {
+            sarif_path = Some(value);
+        } else if let Some(fuzz_target) = FuzzTarget::parse_arg(&arg, &mut args) {
+            targets.push(fuzz_target);
+        } else if arg.starts_with("--") {
+            eprintln!("unknown option \"{}\"", arg);
+            process::exit(1);
+        } else {
+            targets.push(FuzzTarget::bin(arg));
+        }
+    }
+
+    if targets.is_empty() {
+        targets = bin_targets(crate_root)
+            .into_iter()
+            .map(FuzzTarget::bin)
+            .collect();
+    }
+    if targets.is_empty() {
+        eprintln!("no binary target found, please specify them like this \"cargo hfuzz ci [--time SECONDS] [--jobs N] [--junit PATH] [--sarif PATH] [ TARGET|--example NAME|--test NAME|--bench NAME ... ]\"");
+        process::exit(1);
+    }
+
+    let mut checks = Vec::new();
+
+    // every saved crash and every corpus input must not crash anymore
+    let debug_artifacts = build_targets(&targets, crate_root, &BuildType::Debug, config, &options);
+    let executable = |fuzz_target: &FuzzTarget| {
+        fuzz_target
+            .executable(&debug_artifacts)
+            .display()
+            .to_string()
+    };
+    for fuzz_target in &targets {
+        let target = &fuzz_target.name;
+        let target_config = config.target(target);
+        let timeout = replay_timeout(&target_config);
+        let workspace = Path::new(&workspace_dir(&target_config)).join(target);
+        let regressions = campaign::saved_crashes(&workspace)
+            .into_iter()
+            .map(|path| ("crash", path));
+        let mut corpus: Vec<_> = fs::read_dir(input_dir(target, &target_config))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        corpus.sort();
+
+        for (kind, path) in regressions.chain(corpus.into_iter().map(|path| ("corpus", path))) {
+            let crash = replay(&executable(fuzz_target), path, timeout);
+            checks.push(Check {
+                target: target.clone(),
+                name: format!("{} {}", kind, crash.path.display()),
+                failure: (crash.outcome != Outcome::NoCrash).then_some(crash),
+            });
+        }
+    }
+
+    // then look for new crashes
+    let artifacts = build_targets(
+        &targets,
+        crate_root,
+        &BuildType::ReleaseInstrumented,
+        config,
+        &options,
+    );
+    let summaries = fuzz_targets(
+        &targets,
+        &artifacts,
+        config,
+        &options,
+        run_time,
+        jobs,
+        &["--exit_upon_crash".to_string()],
+    );
+    let mut fuzzing_failed = false;
+    for summary in summaries {
+        let name = format!("fuzzing for {}", format_duration(Duration::from_secs(run_time)));
+        if !summary.success {
+            // recorded as a check without a crash, the reports are still written below
+            eprintln!("honggfuzz failed to fuzz {}", summary.target.name);
+            fuzzing_failed = true;
+            checks.push(Check {
+                target: summary.target.name,
+                name: format!("{} (honggfuzz failed)", name),
+                failure: Some(Crash {
+                    path: PathBuf::new(),
+                    size: 0,
+                    outcome: Outcome::Exit(1),
+                    panic: PanicInfo::default(),
+                }),
+            });
+            continue;
+        }
+        let timeout = replay_timeout(&config.target(&summary.target.name));
+        if summary.new_crashes.is_empty() {
+            checks.push(Check {
+                target: summary.target.name,
+                name,
+                failure: None,
+            });
+            continue;
+        }
+        for path in summary.new_crashes {
+            let mut crash = replay(&executable(&summary.target), path, timeout);
+            if crash.outcome == Outcome::NoCrash {
+                // not reproducible in the debug build, still a crash found by honggfuzz
+                crash.outcome = Outcome::Exit(1);
+            }
+            checks.push(Check {
+                target: summary.target.name.clone(),
+                name: format!("{} found {}", name, crash.path.display()),
+                failure: Some(crash),
+            });
+        }
+    }
+
+    for (path, content) in [(junit_path, ci::junit(&checks)), (sarif_path, ci::sarif(&checks))] {
+        if let Some(path) = path {
+            fs::write(&path, content).unwrap_or_else(|e| {
+                eprintln!("error: failed to write \"{}\": {}", path, e);
+                process::exit(1);
+            });
+        }
+    }
+
+    let failures: Vec<_> = checks
+        .iter()
+        .filter_map(|check| check.failure.as_ref().map(|crash| (check, crash)))
+        .collect();
+    println!("{} checks, {} failures", checks.len(), failures.len());
+    for (check, crash) in &failures {
+        println!(
+            "FAILED {}: {} ({} at {})",
+            check.target,
+            check.name,
+            crash.outcome.name(),
+            crash.panic.location.as_deref().unwrap_or("unknown location")
+        );
+    }
+    if !failures.is_empty() || fuzzing_failed {
+        process::exit(1);
+    }
+}
+
+fn hfuzz_triage<T>(mut args: T, crate_root: &Path, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    // a binary unless `--example NAME`, `--test NAME` ... selects another kind of target
+    let arg = args.next().unwrap_or_else(||{
+        eprintln!("please specify the name of the target like this \"cargo hfuzz triage TARGET|--example NAME|--test NAME|--bench NAME [--json] [--frames N] [--target TRIPLE] [--sanitizer NAME] [ CRASH_FILENAME ... ]\"");
+        process::exit(1);
+    });
+    let fuzz_target =
+        FuzzTarget::parse_arg(&arg, &mut args).unwrap_or_else(|| FuzzTarget::bin(arg));
+    let target = fuzz_target.name.clone();
+
+    let mut json = false;
+    let mut frames = triage::DEFAULT_FRAMES;
+    let mut crash_files = Vec::new();
+    let mut options = BuildOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if options.parse_arg(&arg, &mut args) => {}
+            "--frames" => {
+                frames = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("--frames expects a number of stack frames (eg. --frames 3)");
+                    process::exit(1);
+                });
+            }
+            _ => crash_files.push(PathBuf::from(arg)),
+        }
+    }
+
+    let config = config.target(&target);
+
+    // by default, triage every crash saved by honggfuzz in the target's workspace
+    if crash_files.is_empty() {
+        let honggfuzz_workspace = workspace_dir(&config);
+        let workspace = Path::new(&honggfuzz_workspace).join(&target);
+        crash_files = campaign::saved_crashes(&workspace);
+        if crash_files.is_empty() {
+            eprintln!("no crash file found in \"{}\"", workspace.display());
+            process::exit(1);
+        }
+    }
+
+    triage_crashes(
+        &fuzz_target,
+        crash_files,
+        frames,
+        json,
+        crate_root,
+        &config,
+        &options,
+    );
+}
+
+// replay `crash_files` in the debug build of `fuzz_target` and print them by group
+fn triage_crashes(
+    fuzz_target: &FuzzTarget,
+    crash_files: Vec<PathBuf>,
+    frames: usize,
+    json: bool,
+    crate_root: &Path,
+    config: &TargetConfig,
+    options: &BuildOptions,
+) {
+    let artifacts = hfuzz_build(
+        fuzz_target.build_args().into_iter(),
+        crate_root,
+        &BuildType::Debug,
+        config,
+        options,
+    );
+
+    let executable = fuzz_target.executable(&artifacts).display().to_string();
+
+    let crashes: Vec<_> = crash_files
+        .into_iter()
+        .map(|path| replay(&executable, path, replay_timeout(config)))
+        .collect();
+
+    let groups = triage::group(crashes, frames);
+
+    if json {
+        println!("{}", triage::to_json(&groups));
+        return;
+    }
+
+    println!(
+        "{:>5}  {:>7}  {:>8}  {:<10}  {:<30}  SMALLEST",
+        "GROUP", "CRASHES", "SIZE", "OUTCOME", "LOCATION"
+    );
+    for (i, group) in groups.iter().enumerate() {
+        let smallest = group.smallest();
+        println!(
+            "{:>5}  {:>7}  {:>8}  {:<10}  {:<30}  {}",
+            i + 1,
+            group.crashes.len(),
+            smallest.size,
+            group.outcome.name(),
+            group.location.as_deref().unwrap_or("-"),
+            smallest.path.display()
+        );
+        if let Some(message) = group.message() {
+            println!(
+                "       message: {}",
+                message.replace('\n', "\n                ")
+            );
+        }
+        for frame in &group.frames {
+            println!("       at {}", frame);
+        }
+    }
+}
+
+fn hfuzz_report<T>(mut args: T, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let target = args.next().unwrap_or_else(||{
+        eprintln!("please specify the name of the target like this \"cargo hfuzz report TARGET [ REPORT_FILENAME ]\"");
+        process::exit(1);
+    });
+
+    let honggfuzz_workspace = workspace_dir(&config.target(&target));
+    let report_path = args.next().map_or_else(
+        || {
+            Path::new(&honggfuzz_workspace)
+                .join(&target)
+                .join(report::REPORT_FILENAME)
+        },
+        PathBuf::from,
+    );
+
+    let crashes = report::read(&report_path).unwrap_or_else(|e| {
+        eprintln!("error: failed to read \"{}\": {}", report_path.display(), e);
+        process::exit(1);
+    });
+
+    println!("{}", report::to_json(&crashes));
+}
+
+fn hfuzz_stats<T>(mut args: T, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let target = args.next().unwrap_or_else(||{
+        eprintln!("please specify the name of the target like this \"cargo hfuzz stats TARGET [--watch] [--json]\"");
+        process::exit(1);
+    });
+
+    let mut watch = false;
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--watch" => watch = true,
+            "--json" => json = true,
+            _ => {
+                eprintln!("unknown option \"{}\"", arg);
+                process::exit(1);
+            }
+        }
+    }
+
+    let honggfuzz_workspace = workspace_dir(&config.target(&target));
+    let stats_path = Path::new(&honggfuzz_workspace)
+        .join(&target)
+        .join(stats::STATS_FILENAME);
+
+    // only clear the screen between snapshots when a person is looking at it
+    let clear = watch && std::io::stdout().is_terminal();
+
+    loop {
+        let snapshot = match stats::read_last(&stats_path) {
+            Ok(snapshot) => snapshot,
+            Err(e) if !watch => {
+                eprintln!(
+                    "error: failed to read \"{}\": {}, was the target run with \"cargo hfuzz run --stats-file {}\"?",
+                    stats_path.display(),
+                    e,
+                    target
+                );
+                process::exit(1);
+            }
+            Err(_) => None,
+        };
+
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        match snapshot {
+            Some(snapshot) if json => println!("{}", snapshot.to_json()),
+            Some(snapshot) => {
+                println!("target:            {}", target);
+                println!(
+                    "executions:        {} ({}/sec)",
+                    snapshot.total_exec, snapshot.exec_per_sec
+                );
+                println!(
+                    "coverage:          {} edges, {} blocks",
+                    snapshot.edge_cov, snapshot.block_cov
+                );
+                println!(
+                    "crashes:           {} ({} unique)",
+                    snapshot.crashes, snapshot.unique_crashes
+                );
+                println!("timeouts:          {}", snapshot.hangs);
+                println!(
+                    "last new coverage: {} ago",
+                    format_duration(snapshot.since_last_cov(SystemTime::now()))
+                );
+            }
+            None => println!("waiting for honggfuzz to write \"{}\"", stats_path.display()),
+        }
+        let _ = std::io::stdout().flush();
+
+        if !watch {
+            break;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+// `ENGINE PATH TARGET [--link]` of `cargo hfuzz import` and `cargo hfuzz export`
+fn corpus_args<T>(
+    mut args: T,
+    command: &str,
+    engine_option: &str,
+) -> (Engine, PathBuf, String, bool)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let usage = || {
+        eprintln!(
+            "please specify the engine, path and target like this \"cargo hfuzz {} {} cargo-fuzz|afl PATH TARGET [--link]\"",
+            command, engine_option
+        );
+        process::exit(1);
+    };
 
-    while !path.join("Cargo.toml").is_file() {
-        // move to parent path
-        path = match path.parent() {
-            Some(parent) => parent.into(),
-            None => return None, // early return
-        };
+    let mut engine = None;
+    let mut link = false;
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        if let Some(name) = option_value(&arg, engine_option, &mut args) {
+            engine = Some(Engine::from_name(&name).unwrap_or_else(|| {
+                eprintln!(
+                    "unknown engine \"{}\", possible engines are: cargo-fuzz, afl",
+                    name
+                );
+                process::exit(1);
+            }));
+        } else if arg == "--link" {
+            link = true;
+        } else if arg.starts_with("--") {
+            eprintln!("unknown option \"{}\"", arg);
+            process::exit(1);
+        } else {
+            positional.push(arg);
+        }
     }
 
-    Some(path)
+    match (engine, <[String; 2]>::try_from(positional)) {
+        (Some(engine), Ok([path, target])) => (engine, PathBuf::from(path), target, link),
+        _ => usage(),
+    }
 }
 
-fn debugger_command(target: &str) -> Command {
-    let debugger = env::var("HFUZZ_DEBUGGER").unwrap_or_else(|_| "rust-lldb".into());
-    let honggfuzz_target = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| HONGGFUZZ_TARGET.into());
+fn copy_dictionaries(dictionaries: &[PathBuf], dest: &Path) -> Vec<PathBuf> {
+    let mut copies = Vec::new();
+    for dictionary in dictionaries {
+        let copy = dest.join(dictionary.file_name().unwrap());
+        if let Err(e) = fs::create_dir_all(dest).and_then(|_| fs::copy(dictionary, &copy)) {
+            eprintln!("error: failed to copy \"{}\": {}", dictionary.display(), e);
+            process::exit(1);
+        }
+        copies.push(copy);
+    }
+    copies
+}
 
-    let mut cmd = Command::new(&debugger);
+fn hfuzz_import<T>(args: T, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let (engine, path, target, link) = corpus_args(args, "import", "--from");
+    let target_config = config.target(&target);
 
-    match Path::new(&debugger)
-        .file_name()
-        .map(|f| f.to_string_lossy().contains("lldb"))
-    {
-        Some(true) => {
-            cmd.args([
-                "-o",
-                "b rust_panic",
-                "-o",
-                "r",
-                "-o",
-                "bt",
-                "-f",
-                &format!("{}/{}/debug/{}", &honggfuzz_target, target_triple(), target),
-                "--",
-            ]);
+    let sources = engine.input_dirs(&path, &target);
+    let input = PathBuf::from(input_dir(&target, &target_config));
+    let stats = corpus::copy_unique(&sources, &input, link).unwrap_or_else(|e| {
+        eprintln!("error: failed to import \"{}\": {}", path.display(), e);
+        process::exit(1);
+    });
+    println!(
+        "imported {} inputs into \"{}\" ({} duplicates skipped)",
+        stats.copied,
+        input.display(),
+        stats.duplicates
+    );
+
+    // dictionaries usually sit next to the corpus (`fuzz/*.dict`) or in the input directories
+    let mut dictionaries = corpus::dictionaries(&path);
+    for source in &sources {
+        dictionaries.extend(corpus::dictionaries(source));
+    }
+    dictionaries.dedup();
+    let workspace = Path::new(&workspace_dir(&target_config)).join(&target);
+    for copy in copy_dictionaries(&dictionaries, &workspace) {
+        println!(
+            "imported dictionary \"{}\", use it with the `dictionary` setting or HFUZZ_RUN_ARGS=\"-w {}\"",
+            copy.display(),
+            copy.display()
+        );
+    }
+}
+
+fn hfuzz_export<T>(args: T, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let (engine, path, target, link) = corpus_args(args, "export", "--to");
+    let target_config = config.target(&target);
+
+    let input = PathBuf::from(input_dir(&target, &target_config));
+    let output = engine.output_dir(&path, &target);
+    let stats =
+        corpus::copy_unique(std::slice::from_ref(&input), &output, link).unwrap_or_else(|e| {
+            eprintln!("error: failed to export \"{}\": {}", input.display(), e);
+            process::exit(1);
+        });
+    println!(
+        "exported {} inputs to \"{}\" ({} duplicates skipped)",
+        stats.copied,
+        output.display(),
+        stats.duplicates
+    );
+
+    if let Some(dictionary) = &target_config.dictionary {
+        for copy in copy_dictionaries(std::slice::from_ref(dictionary), &path) {
+            println!("exported dictionary \"{}\"", copy.display());
+        }
+    }
+}
+
+// stdout of a successful command, `None` if it could not run or failed
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn hfuzz_bundle<T>(mut args: T, crate_root: &Path, config: &Config)
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let mut archive = None;
+    let mut options = BuildOptions::default();
+    let mut fuzz_target = None;
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        if options.parse_arg(&arg, &mut args) {
+            continue;
+        }
+        if let Some(path) = option_value(&arg, "--output", &mut args) {
+            archive = Some(PathBuf::from(path));
+        } else if let Some(selected) = FuzzTarget::parse_arg(&arg, &mut args) {
+            fuzz_target = Some(selected);
+        } else if arg.starts_with("--") {
+            eprintln!("unknown option \"{}\"", arg);
+            process::exit(1);
+        } else {
+            positional.push(arg);
         }
+    }
+    // a binary unless `--example NAME`, `--test NAME` ... selected another kind of target
+    if fuzz_target.is_none() && !positional.is_empty() {
+        fuzz_target = Some(FuzzTarget::bin(positional.remove(0)));
+    }
+    let (fuzz_target, crash_file) = match (fuzz_target, <[String; 1]>::try_from(positional)) {
+        (Some(fuzz_target), Ok([crash_file])) => (fuzz_target, crash_file),
         _ => {
-            cmd.args([
-                "-ex",
-                "b rust_panic",
-                "-ex",
-                "r",
-                "-ex",
-                "bt",
-                "--args",
-                &format!("{}/{}/debug/{}", &honggfuzz_target, target_triple(), target),
-            ]);
+            eprintln!("please specify the target and the crash file like this \"cargo hfuzz bundle [--output FILE] [--target TRIPLE] [--sanitizer NAME] TARGET|--example NAME|--test NAME|--bench NAME CRASH_FILENAME\"");
+            process::exit(1);
         }
+    };
+    let target = fuzz_target.name.clone();
+    let crash_file = PathBuf::from(crash_file);
+    if !crash_file.is_file() {
+        eprintln!("error: crash file \"{}\" not found", crash_file.display());
+        process::exit(1);
     }
+    let crash_name = crash_file
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
 
-    cmd
-}
+    let config = config.target(&target);
+    let archive = archive.unwrap_or_else(|| {
+        Path::new(&workspace_dir(&config))
+            .join(&target)
+            .join("bundles")
+            .join(format!("{}.tar.gz", crash_name))
+    });
 
-fn hfuzz_version() {
-    println!("cargo-hfuzz {}", VERSION);
+    let artifacts = hfuzz_build(
+        fuzz_target.build_args().into_iter(),
+        crate_root,
+        &BuildType::Debug,
+        &config,
+        &options,
+    );
+
+    let executable = fuzz_target.executable(&artifacts).display().to_string();
+    let timeout = replay_timeout(&config);
+    let (outcome, mut output) = replay_output(&executable, &crash_file, timeout);
+    if outcome == Outcome::Timeout {
+        // the target was killed, nothing in its own output tells it
+        output.extend(
+            format!(
+                "\ncargo-hfuzz: killed after the {} timeout\n",
+                format_duration(timeout)
+            )
+            .bytes(),
+        );
+    }
+    if outcome == Outcome::NoCrash {
+        eprintln!(
+            "warning: \"{}\" does not crash the debug build of {}, bundling it anyway",
+            crash_file.display(),
+            target
+        );
+    }
+
+    let info = BundleInfo {
+        target: target.clone(),
+        crash_file: crash_name.clone(),
+        triple: options.target_triple(),
+        sanitizer: options
+            .sanitizer
+            .map(|sanitizer| sanitizer.name().to_string()),
+        rustc_version: command_output("rustc", &["-vV"]).unwrap_or_default(),
+        rustflags: build_rustflags(&BuildType::Debug, &config, &options),
+        build_args: build_args(&config),
+        git_commit: command_output("git", &["rev-parse", "HEAD"])
+            .map(|commit| commit.trim().to_string()),
+        git_dirty: command_output("git", &["status", "--porcelain"])
+            .is_some_and(|status| !status.trim().is_empty()),
+        cargo_hfuzz_version: VERSION.into(),
+        outcome: outcome.name(),
+        timeout: Some(timeout.as_secs()),
+    };
+
+    // lay the bundle out in the target directory and archive it with tar
+    let staging = Path::new(&options.target_dir())
+        .join("bundle")
+        .join(&target);
+    let _ = fs::remove_dir_all(&staging);
+    let staged = fs::create_dir_all(staging.join(bundle::INPUT_DIR))
+        .and_then(|_| fs::create_dir_all(staging.join(bundle::BIN_DIR)))
+        .and_then(|_| {
+            fs::copy(
+                &crash_file,
+                staging.join(bundle::INPUT_DIR).join(&crash_name),
+            )
+        })
+        .and_then(|_| fs::copy(&executable, staging.join(bundle::BIN_DIR).join(&target)))
+        .and_then(|_| fs::write(staging.join(bundle::OUTPUT_FILENAME), &output))
+        .and_then(|_| fs::write(staging.join(bundle::INFO_FILENAME), info.to_toml()));
+    if let Err(e) = staged {
+        eprintln!(
+            "error: failed to prepare the bundle in \"{}\": {}",
+            staging.display(),
+            e
+        );
+        process::exit(1);
+    }
+
+    if let Some(parent) = archive.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&staging)
+        .arg(".")
+        .status()
+        .unwrap_or_else(|e| {
+            eprintln!("cannot execute tar: {:?}", e);
+            process::exit(1);
+        });
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    println!(
+        "bundled {} ({}) in \"{}\"",
+        crash_name,
+        info.outcome,
+        archive.display()
+    );
 }
 
-fn hfuzz_run<T>(mut args: T, crate_root: &Path, build_type: &BuildType)
+fn hfuzz_unbundle<T>(mut args: T)
 where
     T: std::iter::Iterator<Item = String>,
 {
-    let target = args.next().unwrap_or_else(||{
-        eprintln!("please specify the name of the target like this \"cargo hfuzz run[-debug|-no-instr] TARGET [ ARGS ... ]\"");
+    let mut into = None;
+    let mut debug = false;
+    let mut archive = None;
+    while let Some(arg) = args.next() {
+        if let Some(dir) = option_value(&arg, "--into", &mut args) {
+            into = Some(PathBuf::from(dir));
+        } else if arg == "--debug" {
+            debug = true;
+        } else if arg.starts_with("--") || archive.is_some() {
+            eprintln!("unknown argument \"{}\"", arg);
+            process::exit(1);
+        } else {
+            archive = Some(PathBuf::from(arg));
+        }
+    }
+    let archive = archive.unwrap_or_else(|| {
+        eprintln!("please specify the bundle like this \"cargo hfuzz unbundle [--into DIR] [--debug] BUNDLE\"");
         process::exit(1);
     });
 
-    let honggfuzz_target = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| HONGGFUZZ_TARGET.into());
-    let honggfuzz_workspace =
-        env::var("HFUZZ_WORKSPACE").unwrap_or_else(|_| HONGGFUZZ_WORKSPACE.into());
-    let honggfuzz_input = env::var("HFUZZ_INPUT")
-        .unwrap_or_else(|_| format!("{}/{}/input", honggfuzz_workspace, target));
+    // extract next to the archive by default, `crash.tar.gz` in `crash/`
+    let into = into.unwrap_or_else(|| {
+        let name = archive.file_name().unwrap_or_default().to_string_lossy();
+        match name.strip_suffix(".tar.gz") {
+            Some(stem) => archive.with_file_name(stem),
+            None => archive.with_file_name(format!("{}.d", name)),
+        }
+    });
+    let _ = fs::create_dir_all(&into);
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&into)
+        .status()
+        .unwrap_or_else(|e| {
+            eprintln!("cannot execute tar: {:?}", e);
+            process::exit(1);
+        });
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
 
-    hfuzz_build(
-        vec!["--bin".to_string(), target.clone()].into_iter(),
-        crate_root,
-        build_type,
-    );
+    let info_path = into.join(bundle::INFO_FILENAME);
+    let info = fs::read_to_string(&info_path)
+        .and_then(|text| BundleInfo::parse(&text))
+        .unwrap_or_else(|e| {
+            eprintln!("error: failed to read \"{}\": {}", info_path.display(), e);
+            process::exit(1);
+        });
 
-    match *build_type {
-        BuildType::Debug => {
-            let crash_filename = args.next().unwrap_or_else(||{
-                eprintln!("please specify the crash filename like this \"cargo hfuzz run-debug TARGET CRASH_FILENAME [ ARGS ... ]\"");
-                process::exit(1);
-            });
+    println!("target:      {} ({})", info.target, info.triple);
+    println!("crash file:  {}", info.crash_file);
+    println!("outcome:     {}", info.outcome);
+    if let Some(version) = info.rustc_version.lines().next() {
+        println!("rustc:       {}", version);
+    }
+    if let Some(sanitizer) = &info.sanitizer {
+        println!("sanitizer:   {}", sanitizer);
+    }
+    match &info.git_commit {
+        Some(commit) if info.git_dirty => {
+            println!("git commit:  {} (with uncommitted changes)", commit)
+        }
+        Some(commit) => println!("git commit:  {}", commit),
+        None => {}
+    }
+    println!("rustflags:   {}", info.rustflags);
+    println!("extracted in \"{}\"", into.display());
 
-            let status = debugger_command(&target)
-                .args(args)
-                .env("CARGO_HONGGFUZZ_CRASH_FILENAME", crash_filename)
-                .env(
-                    "RUST_BACKTRACE",
-                    env::var("RUST_BACKTRACE").unwrap_or_else(|_| "1".into()),
-                )
-                .status()
-                .unwrap();
+    let executable = into.join(bundle::BIN_DIR).join(&info.target);
+    let executable = executable.to_string_lossy();
+    let crash_file = into.join(bundle::INPUT_DIR).join(&info.crash_file);
+
+    if debug {
+        if let Some(rr) = rr_debugger(false) {
+            let trace_dir = into.join("rr-trace");
+            let status = rr_record_replay(
+                &rr,
+                &executable,
+                &trace_dir,
+                &crash_file,
+                std::iter::empty(),
+            );
             if !status.success() {
                 process::exit(status.code().unwrap_or(1));
             }
+            return;
         }
-        _ => {
-            // add some flags to sanitizers to make them work with Rust code
-            let asan_options = env::var("ASAN_OPTIONS").unwrap_or_default();
-            let asan_options = format!("detect_odr_violation=0:{}", asan_options);
-
-            let tsan_options = env::var("TSAN_OPTIONS").unwrap_or_default();
-            let tsan_options = format!("report_signal_unsafe=0:{}", tsan_options);
-
-            // get user-defined args for honggfuzz
-            let hfuzz_run_args = env::var("HFUZZ_RUN_ARGS").unwrap_or_default();
-            // FIXME: we split by whitespace without respecting escaping or quotes
-            let hfuzz_run_args = hfuzz_run_args.split_whitespace();
-
-            // get user-defined args for building
-            let hfuzz_build_args = env::var("HFUZZ_BUILD_ARGS").unwrap_or_default();
-            // FIXME: we split by whitespace without respecting escaping or quotes
-            let hfuzz_build_args: Vec<_> = hfuzz_build_args.split_whitespace().collect();
-
-            let hfuzz_build_profile =
-                if let Some(arg) = hfuzz_build_args.iter().find(|f| f.starts_with("--profile")) {
-                    let parts: Vec<_> = arg.splitn(2, '=').collect();
-                    parts
-                        .get(1)
-                        .expect("--profile not in correct format (eg. --profile=<label>)")
-                        .to_string()
-                } else {
-                    "release".to_string()
-                };
-
-            fs::create_dir_all(format!("{}/{}/input", &honggfuzz_workspace, target))
-                .unwrap_or_else(|_| {
-                    println!(
-                        "error: failed to create \"{}/{}/input\"",
-                        &honggfuzz_workspace, target
-                    );
-                });
+        let status = debugger_command(&executable)
+            .envs(sanitizer_options())
+            .env("CARGO_HONGGFUZZ_CRASH_FILENAME", &crash_file)
+            .env(
+                "RUST_BACKTRACE",
+                env::var("RUST_BACKTRACE").unwrap_or_else(|_| "1".into()),
+            )
+            .status()
+            .unwrap();
+        if !status.success() {
+            process::exit(status.code().unwrap_or(1));
+        }
+        return;
+    }
 
-            let command = format!("{}/honggfuzz", &honggfuzz_target);
-            let err = Command::new(&command) // exec honggfuzz replacing current process
-                .args([
-                    "-W",
-                    &format!("{}/{}", &honggfuzz_workspace, target),
-                    "-f",
-                    &honggfuzz_input,
-                    "-P",
-                ])
-                .args(hfuzz_run_args) // allows user-specified arguments to be given to honggfuzz
-                .args([
-                    "--",
-                    &format!(
-                        "{}/{}/{}/{}",
-                        &honggfuzz_target,
-                        target_triple(),
-                        hfuzz_build_profile,
-                        target
-                    ),
-                ])
-                .args(args)
-                .env("ASAN_OPTIONS", asan_options)
-                .env("TSAN_OPTIONS", tsan_options)
-                .exec();
+    // with the timeout of the bundled replay, so that a timeout is reproduced as one
+    let timeout = Duration::from_secs(info.timeout.unwrap_or(DEFAULT_REPLAY_TIMEOUT));
+    let (outcome, output) = replay_output(&executable, &crash_file, timeout);
+    std::io::stderr().write_all(&output).unwrap();
+    let outcome = outcome.name();
+    if outcome == info.outcome {
+        println!("reproduced: {}", outcome);
+    } else {
+        println!("not reproduced: expected {}, got {}", info.outcome, outcome);
+        process::exit(1);
+    }
+}
 
-            // code flow will only reach here if honggfuzz failed to execute
-            eprintln!(
-                "cannot execute {}, try to execute \"cargo hfuzz build\" from fuzzed project directory",
-                &command
-            );
-            eprintln!("{:?}", err);
-            process::exit(1);
-        }
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+// flags instrumenting a crate for coverage feedback with sanitizer-coverage `features`
+fn sancov_flags(features: &[String]) -> String {
+    let mut flags = String::new();
+
+    // The new LLVM pass manager was not enabled in rustc 1.57 as expected:
+    // https://github.com/rust-lang/rust/pull/91263
+    // The fix for now is to pass `-C passes=sancov-module` only to
+    // compilers for which the LLVM version is >= 13.
+    let version_meta = rustc_version::version_meta().unwrap();
+    if version_meta.llvm_version.is_none_or(|v| v.major >= 13) {
+        flags.push_str("-C passes=sancov-module ");
+    } else {
+        flags.push_str("-C passes=sancov ");
     }
+
+    // level 4 is edges and indirect calls
+    if features.iter().any(|feature| feature == "indirect-calls") {
+        flags.push_str("-C llvm-args=-sanitizer-coverage-level=4 ");
+    } else {
+        flags.push_str("-C llvm-args=-sanitizer-coverage-level=3 ");
+    }
+    for feature in features.iter().filter(|feature| *feature != "indirect-calls") {
+        flags.push_str(&format!("-C llvm-args=-sanitizer-coverage-{} ", feature));
+    }
+
+    flags
 }
 
-fn hfuzz_build<T>(args: T, crate_root: &Path, build_type: &BuildType)
+// Run as `RUSTC_WRAPPER` by builds limiting coverage instrumentation to some
+// crates: `args` is the rustc command line, the sancov flags are added to it
+// for the crates matching the filter.
+fn rustc_wrapper<T>(mut args: T) -> !
 where
     T: std::iter::Iterator<Item = String>,
 {
-    let honggfuzz_target = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| HONGGFUZZ_TARGET.into());
+    let rustc = args.next().unwrap_or_else(|| {
+        eprintln!("cargo-hfuzz: rustc wrapper called without a rustc command line");
+        process::exit(1);
+    });
+    let mut args: Vec<_> = args.collect();
+
+    let list = |var: &str| -> Vec<String> {
+        env::var(var)
+            .unwrap_or_default()
+            .split(',')
+            .filter(|pattern| !pattern.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let filter = TargetConfig {
+        instrument: list("CARGO_HONGGFUZZ_INSTRUMENT"),
+        no_instrument: list("CARGO_HONGGFUZZ_NO_INSTRUMENT"),
+        ..TargetConfig::default()
+    };
+
+    // build scripts and proc-macros are built for the host without `--target`
+    let crate_name = args
+        .iter()
+        .position(|arg| arg == "--crate-name")
+        .and_then(|i| args.get(i + 1));
+    let for_target = args.iter().any(|arg| arg == "--target");
+    if for_target && crate_name.is_some_and(|name| filter.instruments(name)) {
+        let flags = env::var("CARGO_HONGGFUZZ_SANCOV_FLAGS").unwrap_or_default();
+        args.extend(flags.split_whitespace().map(String::from));
+    }
+
+    // chain to the wrapper the user had set, e.g. sccache
+    let mut command = match env::var("CARGO_HONGGFUZZ_RUSTC_WRAPPER") {
+        Ok(wrapper) if !wrapper.is_empty() => {
+            let mut command = Command::new(wrapper);
+            command.arg(&rustc);
+            command
+        }
+        _ => Command::new(&rustc),
+    };
+    let err = command.args(args).exec();
+    eprintln!("cargo-hfuzz: cannot execute {}: {:?}", rustc, err);
+    process::exit(1);
+}
 
+// Run as `CC`/`CXX` of builds instrumenting C and C++ code: `compiler` and `args`
+// are the compiler command line, the coverage flags are added to it when the
+// build script belongs to a fuzzed crate. Build scripts of build dependencies and
+// proc-macros don't get the fuzzing `RUSTFLAGS` in `CARGO_ENCODED_RUSTFLAGS`.
+fn cc_wrapper<T>(compiler: String, args: T) -> !
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    let rustflags: Vec<_> = rustflags.split('\x1f').collect();
+    let fuzzed = rustflags
+        .windows(2)
+        .any(|flag| flag == ["--cfg", "fuzzing"]);
+
+    let mut command = Command::new(&compiler);
+    if fuzzed {
+        // first, so that the command line can still override them
+        let flags = env::var("CARGO_HONGGFUZZ_CC_FLAGS").unwrap_or_default();
+        command.args(flags.split_whitespace());
+    }
+    let err = command.args(args).exec();
+    eprintln!("cargo-hfuzz: cannot execute {}: {:?}", compiler, err);
+    process::exit(1);
+}
+
+// RUSTFLAGS of a build, user provided flags included
+fn build_rustflags(
+    build_type: &BuildType,
+    config: &TargetConfig,
+    options: &BuildOptions,
+) -> String {
     // HACK: workaround for rustc < 1.87 linking issues with sanitizers.
     // Since MSRV is 1.85, this is only relevant for older nightly releases.
     // See https://github.com/rust-lang/rust/issues/53945#issuecomment-426824324
@@ -246,7 +2619,6 @@ where
     rustflags.push_str("-C overflow-checks=y ");
     rustflags.push_str("-C force-frame-pointers=y ");
 
-    let mut cargo_incremental = "1";
     match *build_type {
         BuildType::Debug => {
             rustflags.push_str("--cfg fuzzing_debug ");
@@ -263,7 +2635,6 @@ where
             rustflags.push_str("-C codegen-units=1 ");
             rustflags.push_str("-C link-dead-code ");
             //rustflags.push_str("-Coverflow-checks=off ");
-            cargo_incremental = "0";
         }
 
         _ => {
@@ -272,26 +2643,17 @@ where
             rustflags.push_str("-C debuginfo=0 ");
 
             if *build_type == BuildType::ReleaseInstrumented {
-                // The new LLVM pass manager was not enabled in rustc 1.57 as expected:
-                // https://github.com/rust-lang/rust/pull/91263
-                // The fix for now is to pass `-C passes=sancov-module` only to
-                // compilers for which the LLVM version is >= 13.
-                let version_meta = rustc_version::version_meta().unwrap();
-                if version_meta.llvm_version.is_none_or(|v| v.major >= 13) {
-                    rustflags.push_str("-C passes=sancov-module ");
+                if config.filters_instrumentation() {
+                    // the sancov flags are added by `rustc_wrapper` to the matching crates only,
+                    // the filter is recorded here so that changing it rebuilds everything
+                    rustflags.push_str(&format!(
+                        "--cfg fuzzing_instrumentation_filter=\"instrument={};no_instrument={};coverage={}\" ",
+                        config.instrument.join(","),
+                        config.no_instrument.join(","),
+                        options.coverage(config).join(",")
+                    ));
                 } else {
-                    rustflags.push_str("-C passes=sancov ");
-                }
-
-                rustflags.push_str("-C llvm-args=-sanitizer-coverage-level=4 "); // enables indirect calls
-                rustflags.push_str("-C llvm-args=-sanitizer-coverage-trace-pc-guard ");
-                rustflags.push_str("-C llvm-args=-sanitizer-coverage-trace-divs ");
-                rustflags.push_str("-C llvm-args=-sanitizer-coverage-trace-geps ");
-                rustflags.push_str("-C llvm-args=-sanitizer-coverage-stack-depth ");
-
-                // trace-compares doesn't work on macOS without a sanitizer
-                if cfg!(not(target_os = "macos")) {
-                    rustflags.push_str("-C llvm-args=-sanitizer-coverage-trace-compares ");
+                    rustflags.push_str(&sancov_flags(&options.coverage(config)));
                 }
 
                 // HACK: temporary fix, see https://github.com/rust-lang/rust/issues/53945#issuecomment-426824324
@@ -302,47 +2664,186 @@ where
         }
     }
 
-    // add user provided flags
+    if let Some(sanitizer) = options.sanitizer {
+        rustflags.push_str(&format!("-Z sanitizer={} ", sanitizer.name()));
+        rustflags.push_str("-C unsafe-allow-abi-mismatch=sanitizer ");
+        if sanitizer == Sanitizer::Memory {
+            rustflags.push_str("-Z sanitizer-memory-track-origins ");
+            rustflags.push_str("--cfg fuzzing_msan "); // lets honggfuzz unpoison what libhfuzz writes
+        }
+    }
+
+    // add user provided flags, `RUSTFLAGS` comes last to override the `rustflags` setting
+    for flag in &config.rustflags {
+        rustflags.push_str(flag);
+        rustflags.push(' ');
+    }
     rustflags.push_str(&env::var("RUSTFLAGS").unwrap_or_default());
+    rustflags
+}
 
-    // get user-defined args for building
-    let hfuzz_build_args = env::var("HFUZZ_BUILD_ARGS").unwrap_or_default();
-    // FIXME: we split by whitespace without respecting escaping or quotes
-    let mut hfuzz_build_args = hfuzz_build_args.split_whitespace();
+fn hfuzz_build<T>(
+    args: T,
+    crate_root: &Path,
+    build_type: &BuildType,
+    config: &TargetConfig,
+    options: &BuildOptions,
+) -> Vec<Artifact>
+where
+    T: std::iter::Iterator<Item = String>,
+{
+    let honggfuzz_target = options.target_dir();
+
+    // sanitizers are only available with a nightly toolchain
+    if let Some(sanitizer) = options.sanitizer {
+        let nightly = rustc_version::version_meta()
+            .is_ok_and(|v| matches!(v.channel, Channel::Nightly | Channel::Dev));
+        if !nightly {
+            eprintln!(
+                "the {} sanitizer requires a nightly toolchain, try \"cargo +nightly hfuzz ...\"",
+                sanitizer.name()
+            );
+            process::exit(1);
+        }
+    }
+
+    let rustflags = build_rustflags(build_type, config, options);
+    let cargo_incremental = if *build_type == BuildType::ProfileWithGrcov {
+        "0"
+    } else {
+        "1"
+    };
+
+    // the profile is given once, whether it comes from `--profile` or the build arguments
+    let (_, hfuzz_build_args) = split_profile(build_args(config));
+    let profile = options.profile(build_type, config);
 
     let cargo_bin = env::var("CARGO").unwrap_or_else(|_| "cargo".into());
     let mut command = Command::new(cargo_bin);
     command
-        .args(["build", "--target", &target_triple()]) // HACK to avoid building build scripts with rustflags
+        .args(["build", "--target", &options.target_triple()]) // HACK to avoid building build scripts with rustflags
         .args(args)
-        .args(hfuzz_build_args.clone()) // allows user-specified arguments to be given to cargo build
+        .args(&hfuzz_build_args) // allows user-specified arguments to be given to cargo build
+        .args(["--profile", &profile])
         .env("RUSTFLAGS", rustflags)
         .env("CARGO_INCREMENTAL", cargo_incremental)
         .env("CARGO_TARGET_DIR", &honggfuzz_target) // change target_dir to not clash with regular builds
         .env("CRATE_ROOT", crate_root);
 
-    if *build_type == BuildType::ProfileWithGrcov {
+    if *build_type == BuildType::ReleaseInstrumented && config.filters_instrumentation() {
+        let wrapper = env::current_exe().unwrap_or_else(|e| {
+            eprintln!("cannot find the cargo-hfuzz executable: {:?}", e);
+            process::exit(1);
+        });
         command
-            .env("CARGO_HONGGFUZZ_BUILD_VERSION", VERSION) // used by build.rs to check that versions are in sync
-            .env("CARGO_HONGGFUZZ_TARGET_DIR", &honggfuzz_target); // env variable to be read by build.rs script
+            .env("RUSTC_WRAPPER", wrapper) // see `rustc_wrapper`
+            .env(
+                "CARGO_HONGGFUZZ_RUSTC_WRAPPER",
+                env::var("RUSTC_WRAPPER").unwrap_or_default(),
+            )
+            .env(
+                "CARGO_HONGGFUZZ_SANCOV_FLAGS",
+                sancov_flags(&options.coverage(config)),
+            )
+            .env("CARGO_HONGGFUZZ_INSTRUMENT", config.instrument.join(","))
+            .env("CARGO_HONGGFUZZ_NO_INSTRUMENT", config.no_instrument.join(","));
     }
-    // to place honggfuzz executable at a known location
-    else if *build_type != BuildType::Debug {
-        // ensure we do not set --release when one of the build args
-        // contains a --profile
-        if !hfuzz_build_args.any(|f| f.starts_with("--profile")) {
-            command.arg("--release");
+
+    if *build_type == BuildType::ReleaseInstrumented
+        && (options.instrument_c || config.instrument_c == Some(true))
+    {
+        // C and C++ code compiled by build scripts with the `cc` crate, which reads
+        // `CC_<triple>` ... before `CC` ..., gets the same coverage features as Rust code.
+        // The compilers are run through `cc_wrapper`, as build dependencies and
+        // proc-macros are built for the same triple but must not be instrumented.
+        let triple = options.target_triple().replace('-', "_");
+        let wrapper = env::current_exe().unwrap_or_else(|e| {
+            eprintln!("cannot find the cargo-hfuzz executable: {:?}", e);
+            process::exit(1);
+        });
+        let mut compilers = Vec::new();
+        for (compiler, default) in [("CC", "clang"), ("CXX", "clang++")] {
+            let compiler_var = format!("{}_{}", compiler, triple);
+            let user_compiler = env::var(&compiler_var)
+                .or_else(|_| env::var(compiler))
+                .unwrap_or_else(|_| default.into());
+            compilers.extend(user_compiler.split_whitespace().next().map(String::from));
+            command.env(
+                &compiler_var,
+                format!("{} {}", wrapper.display(), user_compiler),
+            );
         }
+        command
+            .env("CC_KNOWN_WRAPPER_CUSTOM", wrapper.file_stem().unwrap()) // lets `cc` see the real compiler
+            .env("CARGO_HONGGFUZZ_CC", compilers.join(" "))
+            .env(
+                "CARGO_HONGGFUZZ_CC_FLAGS",
+                config::clang_coverage_flag(&options.coverage(config)),
+            );
+    }
 
+    // to place honggfuzz executable at a known location
+    if *build_type != BuildType::Debug {
         command
             .env("CARGO_HONGGFUZZ_BUILD_VERSION", VERSION) // used by build.rs to check that versions are in sync
             .env("CARGO_HONGGFUZZ_TARGET_DIR", &honggfuzz_target); // env variable to be read by build.rs script
-    } // to place honggfuzz executable at a known location
+    }
+
+    if options.sanitizer == Some(Sanitizer::Memory) {
+        // MemorySanitizer reports every read of memory written by uninstrumented
+        // code, so the standard library has to be rebuilt with it
+        let sysroot = Command::new("rustc")
+            .args(["--print", "sysroot"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_default();
+        if !Path::new(&sysroot).join("lib/rustlib/src/rust/library").is_dir() {
+            eprintln!(
+                "the memory sanitizer needs the standard library sources, try \"rustup component add rust-src --toolchain nightly\""
+            );
+            process::exit(1);
+        }
+        command.arg("-Zbuild-std");
+    }
 
-    let status = command.status().unwrap();
+    // cargo tells where it put the executables in its JSON messages, the diagnostics
+    // are still rendered on stderr
+    let mut child = command
+        .arg("--message-format=json-render-diagnostics")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut artifacts = Vec::new();
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        let line = line.unwrap();
+        match Artifact::from_message(&line) {
+            Some(artifact) => artifacts.push(artifact),
+            None if !line.starts_with('{') => println!("{}", line),
+            None => {}
+        }
+    }
+    let status = child.wait().unwrap();
     if !status.success() {
         process::exit(status.code().unwrap_or(1));
     }
+
+    // record the sanitizer-coverage features next to the binaries, for the tools replaying them
+    if *build_type == BuildType::ReleaseInstrumented {
+        let coverage_path = options
+            .profile_dir(build_type, config)
+            .join(COVERAGE_FILENAME);
+        let mut features =
+            String::from("# sanitizer-coverage features of the instrumented build\n");
+        for feature in options.coverage(config) {
+            features.push_str(&feature);
+            features.push('\n');
+        }
+        if let Err(e) = fs::write(&coverage_path, features) {
+            eprintln!("warning: failed to write \"{}\": {}", coverage_path.display(), e);
+        }
+    }
+
+    artifacts
 }
 
 fn hfuzz_clean<T>(args: T)
@@ -365,12 +2866,35 @@ where
 fn main() {
     // TODO: maybe use `clap` crate
 
-    let mut args = env::args().skip(1);
+    let mut args = env::args().skip(1).peekable();
+
+    // cargo-hfuzz is the C and C++ compiler of build scripts with `--instrument-c`
+    if let Ok(compilers) = env::var("CARGO_HONGGFUZZ_CC") {
+        if let Some(compiler) = args.next_if(|arg| compilers.split(' ').any(|c| c == arg)) {
+            cc_wrapper(compiler, args);
+        }
+    }
+
+    // cargo-hfuzz is its own `RUSTC_WRAPPER` when instrumenting some crates only
+    if env::var_os("CARGO_HONGGFUZZ_SANCOV_FLAGS").is_some() {
+        rustc_wrapper(args);
+    }
+
     if args.next() != Some("hfuzz".to_string()) {
         eprintln!("please launch as a cargo subcommand: \"cargo hfuzz ...\"");
         process::exit(1);
     }
 
+    // bundles are replayed by people who don't have the fuzzed project
+    if args.next_if(|arg| arg == "unbundle").is_some() {
+        hfuzz_unbundle(args);
+        return;
+    }
+    if args.next_if(|arg| arg == "doctor").is_some() {
+        hfuzz_doctor(find_crate_root().as_deref());
+        return;
+    }
+
     // change to crate root to have the same behavior as cargo build/run
     let crate_root = find_crate_root().unwrap_or_else(|| {
         eprintln!(
@@ -380,28 +2904,85 @@ fn main() {
     });
     env::set_current_dir(&crate_root).unwrap();
 
+    let config = Config::load(&crate_root).unwrap_or_else(|e| {
+        eprintln!("error: invalid honggfuzz settings: {}", e);
+        process::exit(1);
+    });
+
     match args.next() {
         Some(ref s) if s == "build" => {
-            hfuzz_build(args, &crate_root, &BuildType::ReleaseInstrumented);
+            let (options, args) = BuildOptions::from_args(args);
+            hfuzz_build(
+                args.into_iter(),
+                &crate_root,
+                &BuildType::ReleaseInstrumented,
+                &config.defaults,
+                &options,
+            );
         }
         Some(ref s) if s == "build-no-instr" => {
-            hfuzz_build(args, &crate_root, &BuildType::ReleaseNotInstrumented);
+            let (options, args) = BuildOptions::from_args(args);
+            hfuzz_build(
+                args.into_iter(),
+                &crate_root,
+                &BuildType::ReleaseNotInstrumented,
+                &config.defaults,
+                &options,
+            );
         }
         Some(ref s) if s == "build-debug" => {
-            hfuzz_build(args, &crate_root, &BuildType::Debug);
+            let (options, args) = BuildOptions::from_args(args);
+            hfuzz_build(
+                args.into_iter(),
+                &crate_root,
+                &BuildType::Debug,
+                &config.defaults,
+                &options,
+            );
         }
         Some(ref s) if s == "build-grcov" => {
-            hfuzz_build(args, &crate_root, &BuildType::ProfileWithGrcov);
+            let (options, args) = BuildOptions::from_args(args);
+            hfuzz_build(
+                args.into_iter(),
+                &crate_root,
+                &BuildType::ProfileWithGrcov,
+                &config.defaults,
+                &options,
+            );
         }
         Some(ref s) if s == "run" => {
-            hfuzz_run(args, &crate_root, &BuildType::ReleaseInstrumented);
+            hfuzz_run(args, &crate_root, &BuildType::ReleaseInstrumented, &config);
         }
         Some(ref s) if s == "run-no-instr" => {
-            hfuzz_run(args, &crate_root, &BuildType::ReleaseNotInstrumented);
+            hfuzz_run(args, &crate_root, &BuildType::ReleaseNotInstrumented, &config);
         }
 
+        Some(ref s) if s == "run-all" => {
+            hfuzz_run_all(args, &crate_root, &config);
+        }
         Some(ref s) if s == "run-debug" => {
-            hfuzz_run(args, &crate_root, &BuildType::Debug);
+            hfuzz_run(args, &crate_root, &BuildType::Debug, &config);
+        }
+        Some(ref s) if s == "ci" => {
+            hfuzz_ci(args, &crate_root, &config);
+        }
+        Some(ref s) if s == "triage" => {
+            hfuzz_triage(args, &crate_root, &config);
+        }
+        Some(ref s) if s == "report" => {
+            hfuzz_report(args, &config);
+        }
+        Some(ref s) if s == "stats" => {
+            hfuzz_stats(args, &config);
+        }
+        Some(ref s) if s == "import" => {
+            hfuzz_import(args, &config);
+        }
+        Some(ref s) if s == "export" => {
+            hfuzz_export(args, &config);
+        }
+        Some(ref s) if s == "bundle" => {
+            hfuzz_bundle(args, &crate_root, &config);
         }
         Some(ref s) if s == "clean" => {
             hfuzz_clean(args);
@@ -411,7 +2992,7 @@ fn main() {
         }
         _ => {
             eprintln!(
-                "possible commands are: run, run-no-instr, run-debug, build, build-no-instr, build-grcov, build-debug, clean, version"
+                "possible commands are: run, run-no-instr, run-debug, run-all, build, build-no-instr, build-grcov, build-debug, ci, triage, report, stats, import, export, bundle, unbundle, clean, doctor, version"
             );
             process::exit(1);
         }
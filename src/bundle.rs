@@ -0,0 +1,162 @@
+//! Crash bundles written by `cargo hfuzz bundle` and replayed by `cargo hfuzz unbundle`.
+//!
+//! A bundle is a `.tar.gz` archive holding everything needed to reproduce a
+//! crash without the fuzzed project: the crashing input, the `fuzzing_debug`
+//! build of the target, its panic output and a [`BundleInfo`] describing how
+//! the binary was built:
+//!
+//! ```text
+//! HFUZZ_BUNDLE.toml
+//! input/<CRASH_FILENAME>
+//! bin/<TARGET>
+//! output.txt
+//! ```
+
+use std::io;
+
+/// Name of the bundle description file at the root of the archive.
+pub const INFO_FILENAME: &str = "HFUZZ_BUNDLE.toml";
+/// Name of the file holding the output of the replayed crash, ending with a
+/// note from cargo-hfuzz when the replay was killed after its timeout.
+pub const OUTPUT_FILENAME: &str = "output.txt";
+/// Directory of the crashing input in the archive.
+pub const INPUT_DIR: &str = "input";
+/// Directory of the target binary in the archive.
+pub const BIN_DIR: &str = "bin";
+
+/// Description of how a bundled crash was produced.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BundleInfo {
+    /// Name of the fuzzed target, also the name of the bundled binary.
+    pub target: String,
+    /// File name of the crashing input.
+    pub crash_file: String,
+    /// Target triple the binary was built for.
+    pub triple: String,
+    /// Sanitizer the binary was built with.
+    pub sanitizer: Option<String>,
+    /// Output of `rustc -vV`.
+    pub rustc_version: String,
+    /// `RUSTFLAGS` of the build.
+    pub rustflags: String,
+    /// Extra arguments given to `cargo build`.
+    pub build_args: Vec<String>,
+    /// Commit of the fuzzed project, `None` outside of a git repository.
+    pub git_commit: Option<String>,
+    /// Whether the working tree had uncommitted changes.
+    pub git_dirty: bool,
+    /// Version of cargo-hfuzz that wrote the bundle.
+    pub cargo_hfuzz_version: String,
+    /// Outcome of the replay, as named by [`Outcome::name`](crate::triage::Outcome::name).
+    pub outcome: String,
+    /// Seconds after which the replay was killed as a timeout.
+    pub timeout: Option<u64>,
+}
+
+impl BundleInfo {
+    /// Serialize the description as the content of `HFUZZ_BUNDLE.toml`.
+    pub fn to_toml(&self) -> String {
+        let mut table = toml::Table::new();
+        let mut insert = |key: &str, value: toml::Value| {
+            table.insert(key.to_string(), value);
+        };
+        insert("target", self.target.clone().into());
+        insert("crash_file", self.crash_file.clone().into());
+        insert("triple", self.triple.clone().into());
+        if let Some(sanitizer) = &self.sanitizer {
+            insert("sanitizer", sanitizer.clone().into());
+        }
+        insert("rustc_version", self.rustc_version.clone().into());
+        insert("rustflags", self.rustflags.clone().into());
+        insert("build_args", self.build_args.clone().into());
+        if let Some(git_commit) = &self.git_commit {
+            insert("git_commit", git_commit.clone().into());
+        }
+        insert("git_dirty", self.git_dirty.into());
+        insert("cargo_hfuzz_version", self.cargo_hfuzz_version.clone().into());
+        insert("outcome", self.outcome.clone().into());
+        if let Some(timeout) = self.timeout {
+            insert("timeout", (timeout as i64).into());
+        }
+        table.to_string()
+    }
+
+    /// Parse the content of `HFUZZ_BUNDLE.toml`.
+    ///
+    /// ```
+    /// use honggfuzz::bundle::BundleInfo;
+    ///
+    /// let info = BundleInfo {
+    ///     target: "example".into(),
+    ///     crash_file: "SIGABRT.PC.7ffff7a42e97.fuzz".into(),
+    ///     triple: "x86_64-unknown-linux-gnu".into(),
+    ///     rustc_version: "rustc 1.85.0 (4d91de4e4 2025-02-17)\nhost: x86_64-unknown-linux-gnu\n".into(),
+    ///     rustflags: "--cfg fuzzing --cfg fuzzing_debug".into(),
+    ///     git_commit: Some("5bcb21e".into()),
+    ///     outcome: "timeout".into(),
+    ///     timeout: Some(10),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(BundleInfo::parse(&info.to_toml()).unwrap(), info);
+    ///
+    /// assert!(BundleInfo::parse("target = 42").is_err());
+    /// let text = "target = \"example\"\ncrash_file = \"input\"\ntriple = \"x86_64-unknown-linux-gnu\"\ntimeout = \"10\"";
+    /// assert!(BundleInfo::parse(text).is_err());
+    /// ```
+    pub fn parse(text: &str) -> io::Result<BundleInfo> {
+        let table: toml::Table = text.parse().map_err(invalid)?;
+        let string = |key: &str| -> io::Result<Option<String>> {
+            match table.get(key) {
+                None => Ok(None),
+                Some(value) => value
+                    .as_str()
+                    .map(|s| Some(s.to_string()))
+                    .ok_or_else(|| invalid(format!("`{}` should be a string", key))),
+            }
+        };
+        let required = |key: &str| -> io::Result<String> {
+            string(key)?.ok_or_else(|| invalid(format!("missing `{}`", key)))
+        };
+        let build_args = match table.get("build_args") {
+            None => Vec::new(),
+            Some(value) => value
+                .as_array()
+                .and_then(|array| {
+                    array
+                        .iter()
+                        .map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .ok_or_else(|| invalid("`build_args` should be an array of strings"))?,
+        };
+        Ok(BundleInfo {
+            target: required("target")?,
+            crash_file: required("crash_file")?,
+            triple: required("triple")?,
+            sanitizer: string("sanitizer")?,
+            rustc_version: string("rustc_version")?.unwrap_or_default(),
+            rustflags: string("rustflags")?.unwrap_or_default(),
+            build_args,
+            git_commit: string("git_commit")?,
+            git_dirty: table
+                .get("git_dirty")
+                .and_then(toml::Value::as_bool)
+                .unwrap_or_default(),
+            cargo_hfuzz_version: string("cargo_hfuzz_version")?.unwrap_or_default(),
+            outcome: string("outcome")?.unwrap_or_default(),
+            timeout: match table.get("timeout") {
+                None => None,
+                Some(value) => Some(
+                    value
+                        .as_integer()
+                        .and_then(|timeout| u64::try_from(timeout).ok())
+                        .ok_or_else(|| invalid("`timeout` should be a number of seconds"))?,
+                ),
+            },
+        })
+    }
+}
+
+fn invalid<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
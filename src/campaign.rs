@@ -0,0 +1,320 @@
+//! Starting and monitoring honggfuzz from Rust code.
+//!
+//! [`Campaign`] builds the `honggfuzz` command line fuzzing an already built
+//! target, so that test harnesses and orchestration tools don't have to know
+//! its arguments. `cargo hfuzz run` is built on it.
+//!
+//! ```no_run
+//! use honggfuzz::campaign::Campaign;
+//! use std::time::Duration;
+//!
+//! let campaign = Campaign::new(
+//!     "hfuzz_target/honggfuzz",
+//!     "hfuzz_target/x86_64-unknown-linux-gnu/release/example",
+//! )
+//! .workspace("hfuzz_workspace/example")
+//! .threads(4)
+//! .run_time(Duration::from_secs(60))
+//! .stats_file("hfuzz_workspace/example/HONGGFUZZ.STATS.CSV");
+//!
+//! let mut child = campaign.spawn()?;
+//! while child.try_wait()?.is_none() {
+//!     if let Some(stats) = campaign.stats()? {
+//!         println!("{} execs/sec, {} crashes", stats.exec_per_sec, stats.unique_crashes);
+//!     }
+//!     std::thread::sleep(Duration::from_secs(1));
+//! }
+//! println!("crashes: {:?}", campaign.crashes());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::stats::{self, Stats};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+/// Options given to the sanitizers to make them work with Rust code, the same
+/// ones `cargo honggfuzz` always set. Only Rust-specific false positives are
+/// silenced here, the sanitizers keep their own defaults otherwise.
+pub const SANITIZER_OPTIONS: &[(&str, &str)] = &[
+    // rustc may emit the same global in several codegen units and crates,
+    // which AddressSanitizer reports as One Definition Rule violations
+    ("ASAN_OPTIONS", "detect_odr_violation=0"),
+    // the stack overflow handler of the standard library is a signal handler
+    // which calls functions that are not async-signal-safe
+    ("TSAN_OPTIONS", "report_signal_unsafe=0"),
+];
+
+/// Builder of a honggfuzz run on a fuzz target.
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    honggfuzz: PathBuf,
+    target: PathBuf,
+    target_args: Vec<OsString>,
+    workspace: Option<PathBuf>,
+    input: Option<PathBuf>,
+    threads: Option<u64>,
+    timeout: Option<Duration>,
+    run_time: Option<Duration>,
+    dictionary: Option<PathBuf>,
+    stats_file: Option<PathBuf>,
+    persistent: bool,
+    save_all: bool,
+    sanitizer_options: Vec<(String, String)>,
+    args: Vec<OsString>,
+}
+
+impl Campaign {
+    /// Fuzz the executable `target` with the `honggfuzz` command at `honggfuzz`.
+    ///
+    /// The target is fuzzed in persistent mode, with the sanitizer options
+    /// of [`SANITIZER_OPTIONS`].
+    pub fn new<P: AsRef<Path>, T: AsRef<Path>>(honggfuzz: P, target: T) -> Campaign {
+        Campaign {
+            honggfuzz: honggfuzz.as_ref().to_path_buf(),
+            target: target.as_ref().to_path_buf(),
+            target_args: Vec::new(),
+            workspace: None,
+            input: None,
+            threads: None,
+            timeout: None,
+            run_time: None,
+            dictionary: None,
+            stats_file: None,
+            persistent: true,
+            save_all: false,
+            sanitizer_options: SANITIZER_OPTIONS
+                .iter()
+                .map(|(var, options)| (var.to_string(), options.to_string()))
+                .collect(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Directory of the crashes and reports (`-W`), the current directory by default.
+    pub fn workspace<P: AsRef<Path>>(mut self, workspace: P) -> Campaign {
+        self.workspace = Some(workspace.as_ref().to_path_buf());
+        self
+    }
+
+    /// Corpus directory (`-f`), `<WORKSPACE>/input` by default.
+    pub fn input<P: AsRef<Path>>(mut self, input: P) -> Campaign {
+        self.input = Some(input.as_ref().to_path_buf());
+        self
+    }
+
+    /// Number of fuzzing threads (`-n`).
+    pub fn threads(mut self, threads: u64) -> Campaign {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Timeout of a single run of the target (`-t`), rounded up to whole
+    /// seconds, and to one second at least as honggfuzz reads `-t 0` as no timeout.
+    ///
+    /// ```
+    /// use honggfuzz::campaign::Campaign;
+    /// use std::time::Duration;
+    ///
+    /// let timeout_arg = |timeout| {
+    ///     let command = Campaign::new("honggfuzz", "example").timeout(timeout).command();
+    ///     let args: Vec<_> = command.get_args().map(|arg| arg.to_str().unwrap().to_string()).collect();
+    ///     let position = args.iter().position(|arg| arg == "-t").unwrap();
+    ///     args[position + 1].clone()
+    /// };
+    /// assert_eq!(timeout_arg(Duration::from_millis(500)), "1");
+    /// assert_eq!(timeout_arg(Duration::from_millis(1500)), "2");
+    /// assert_eq!(timeout_arg(Duration::from_secs(3)), "3");
+    /// assert_eq!(timeout_arg(Duration::ZERO), "1");
+    /// ```
+    pub fn timeout(mut self, timeout: Duration) -> Campaign {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Stop fuzzing after this time (`--run_time`), in whole seconds.
+    pub fn run_time(mut self, run_time: Duration) -> Campaign {
+        self.run_time = Some(run_time);
+        self
+    }
+
+    /// Dictionary of tokens to insert in inputs (`-w`).
+    pub fn dictionary<P: AsRef<Path>>(mut self, dictionary: P) -> Campaign {
+        self.dictionary = Some(dictionary.as_ref().to_path_buf());
+        self
+    }
+
+    /// Write statistics to this file each time there is new coverage (`--statsfile`), read back by [`Campaign::stats`].
+    pub fn stats_file<P: AsRef<Path>>(mut self, stats_file: P) -> Campaign {
+        self.stats_file = Some(stats_file.as_ref().to_path_buf());
+        self
+    }
+
+    /// Whether the target runs the `fuzz!` loop (`-P`), true by default.
+    pub fn persistent(mut self, persistent: bool) -> Campaign {
+        self.persistent = persistent;
+        self
+    }
+
+    /// Save every crash, not only the ones with a new stack (`--save_all`).
+    pub fn save_all(mut self, save_all: bool) -> Campaign {
+        self.save_all = save_all;
+        self
+    }
+
+    /// Add `options` to the sanitizer variable `var` (e.g. `ASAN_OPTIONS`),
+    /// after the defaults of [`SANITIZER_OPTIONS`] so that they override them.
+    pub fn sanitizer_options(mut self, var: &str, options: &str) -> Campaign {
+        if options.is_empty() {
+            return self;
+        }
+        match self
+            .sanitizer_options
+            .iter_mut()
+            .find(|(name, _)| name == var)
+        {
+            Some((_, value)) => *value = format!("{}:{}", value, options),
+            None => self
+                .sanitizer_options
+                .push((var.to_string(), options.to_string())),
+        }
+        self
+    }
+
+    /// Additional honggfuzz arguments, given after the ones of the other settings to override them.
+    pub fn args<I, S>(mut self, args: I) -> Campaign
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    /// Arguments given to the target itself.
+    pub fn target_args<I, S>(mut self, args: I) -> Campaign
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.target_args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    /// The honggfuzz command of the campaign.
+    ///
+    /// ```
+    /// use honggfuzz::campaign::Campaign;
+    /// use std::time::Duration;
+    ///
+    /// let command = Campaign::new("hfuzz_target/honggfuzz", "target/example")
+    ///     .workspace("hfuzz_workspace/example")
+    ///     .threads(2)
+    ///     .timeout(Duration::from_secs(1))
+    ///     .args(["--exit_upon_crash"])
+    ///     .target_args(["--verbose"])
+    ///     .sanitizer_options("ASAN_OPTIONS", "detect_leaks=0")
+    ///     .command();
+    ///
+    /// let args: Vec<_> = command.get_args().map(|arg| arg.to_str().unwrap()).collect();
+    /// assert_eq!(
+    ///     args,
+    ///     [
+    ///         "-W", "hfuzz_workspace/example",
+    ///         "-f", "hfuzz_workspace/example/input",
+    ///         "-P",
+    ///         "-n", "2",
+    ///         "-t", "1",
+    ///         "--exit_upon_crash",
+    ///         "--", "target/example", "--verbose",
+    ///     ]
+    /// );
+    /// assert!(command.get_envs().any(|(var, value)| {
+    ///     var == "ASAN_OPTIONS" && value.unwrap() == "detect_odr_violation=0:detect_leaks=0"
+    /// }));
+    /// ```
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.honggfuzz);
+        if let Some(workspace) = &self.workspace {
+            command.arg("-W").arg(workspace);
+        }
+        command.arg("-f").arg(self.input_dir());
+        if self.persistent {
+            command.arg("-P");
+        }
+        if self.save_all {
+            command.arg("--save_all");
+        }
+        if let Some(stats_file) = &self.stats_file {
+            command.arg("--statsfile").arg(stats_file);
+        }
+        if let Some(threads) = self.threads {
+            command.args(["-n", &threads.to_string()]);
+        }
+        if let Some(timeout) = self.timeout {
+            let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+            command.args(["-t", &secs.max(1).to_string()]);
+        }
+        if let Some(run_time) = self.run_time {
+            command.args(["--run_time", &run_time.as_secs().to_string()]);
+        }
+        if let Some(dictionary) = &self.dictionary {
+            command.arg("-w").arg(dictionary);
+        }
+        command
+            .args(&self.args)
+            .arg("--")
+            .arg(&self.target)
+            .args(&self.target_args)
+            .envs(self.sanitizer_options.clone());
+        command
+    }
+
+    /// Create the workspace and input directories, and start honggfuzz.
+    pub fn spawn(&self) -> io::Result<Child> {
+        if let Some(workspace) = &self.workspace {
+            fs::create_dir_all(workspace)?;
+        }
+        fs::create_dir_all(self.input_dir())?;
+        self.command().spawn()
+    }
+
+    /// Crash files saved in the workspace so far, sorted by name.
+    pub fn crashes(&self) -> Vec<PathBuf> {
+        saved_crashes(self.workspace.as_deref().unwrap_or(Path::new(".")))
+    }
+
+    /// Latest statistics written by honggfuzz, `None` without [`Campaign::stats_file`]
+    /// or before honggfuzz wrote any.
+    pub fn stats(&self) -> io::Result<Option<Stats>> {
+        match &self.stats_file {
+            Some(path) if path.exists() => stats::read_last(path),
+            _ => Ok(None),
+        }
+    }
+
+    fn input_dir(&self) -> PathBuf {
+        match (&self.input, &self.workspace) {
+            (Some(input), _) => input.clone(),
+            (None, Some(workspace)) => workspace.join("input"),
+            (None, None) => PathBuf::from("input"),
+        }
+    }
+}
+
+/// Crash files saved by honggfuzz in the `workspace` directory, sorted by name.
+pub fn saved_crashes(workspace: &Path) -> Vec<PathBuf> {
+    let mut crashes: Vec<_> = fs::read_dir(workspace)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "fuzz"))
+        .collect();
+    crashes.sort();
+    crashes
+}
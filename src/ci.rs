@@ -0,0 +1,214 @@
+//! JUnit XML and SARIF reports written by `cargo hfuzz ci`.
+//!
+//! Every input replayed and every fuzzing run is a [`Check`], failing when it
+//! crashed the target. Failures point at the panic location, so that CI
+//! systems can annotate the offending source line.
+
+use std::fmt::Write;
+
+use crate::json;
+use crate::triage::Crash;
+
+/// A single regression or fuzzing check of a target.
+#[derive(Clone, Debug)]
+pub struct Check {
+    /// Name of the fuzzed target.
+    pub target: String,
+    /// Name of the check, e.g. `corpus hfuzz_workspace/example/input/42`.
+    pub name: String,
+    /// The crash that made the check fail, if any. Its path is empty when no
+    /// input caused the failure, e.g. when honggfuzz itself failed.
+    pub failure: Option<Crash>,
+}
+
+impl Check {
+    /// Panic location of the failure split into `(file, line, column)`.
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use honggfuzz::ci::Check;
+    /// use honggfuzz::triage::{Crash, Outcome, PanicInfo};
+    ///
+    /// let check = Check {
+    ///     target: "example".into(),
+    ///     name: "fuzzing".into(),
+    ///     failure: Some(Crash {
+    ///         path: PathBuf::from("hfuzz_workspace/example/SIGABRT.fuzz"),
+    ///         size: 3,
+    ///         outcome: Outcome::Panic,
+    ///         panic: PanicInfo {
+    ///             location: Some("src/main.rs:36:13".into()),
+    ///             message: Some("BOOM".into()),
+    ///             frames: vec![],
+    ///         },
+    ///     }),
+    /// };
+    /// assert_eq!(check.location(), Some(("src/main.rs", Some(36), Some(13))));
+    /// ```
+    pub fn location(&self) -> Option<(&str, Option<u32>, Option<u32>)> {
+        let location = self.failure.as_ref()?.panic.location.as_deref()?;
+        let mut parts = location.rsplitn(3, ':');
+        let column = parts.next();
+        let line = parts.next();
+        match (parts.next(), line, column) {
+            (Some(file), Some(line), Some(column)) => {
+                Some((file, line.parse().ok(), column.parse().ok()))
+            }
+            _ => Some((location, None, None)),
+        }
+    }
+
+    // one line summary of the failure
+    fn message(&self, crash: &Crash) -> String {
+        let mut message = if crash.path.as_os_str().is_empty() {
+            format!("{} failed", self.target)
+        } else {
+            format!("{} crashed {}", crash.path.display(), self.target)
+        };
+        let _ = write!(message, " ({}", crash.outcome.name());
+        if let Some(location) = &crash.panic.location {
+            let _ = write!(message, " at {}", location);
+        }
+        message.push(')');
+        if let Some(panic_message) = &crash.panic.message {
+            let _ = write!(message, ": {}", panic_message);
+        }
+        message
+    }
+}
+
+/// Serialize checks as a JUnit XML report, with a test suite per target.
+pub fn junit(checks: &[Check]) -> String {
+    let failures = |checks: &[&Check]| checks.iter().filter(|c| c.failure.is_some()).count();
+
+    let mut targets: Vec<&str> = Vec::new();
+    for check in checks {
+        if !targets.contains(&check.target.as_str()) {
+            targets.push(&check.target);
+        }
+    }
+
+    let all: Vec<_> = checks.iter().collect();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"cargo-hfuzz\" tests=\"{}\" failures=\"{}\">",
+        all.len(),
+        failures(&all)
+    );
+    for target in targets {
+        let suite: Vec<_> = checks.iter().filter(|c| c.target == target).collect();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            xml_escape(target),
+            suite.len(),
+            failures(&suite)
+        );
+        for check in suite {
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                xml_escape(target),
+                xml_escape(&check.name)
+            );
+            if let Some((file, line, _)) = check.location() {
+                let _ = write!(xml, " file=\"{}\"", xml_escape(file));
+                if let Some(line) = line {
+                    let _ = write!(xml, " line=\"{}\"", line);
+                }
+            }
+            match &check.failure {
+                None => xml.push_str("/>\n"),
+                Some(crash) => {
+                    let mut details = check.message(crash);
+                    for frame in &crash.panic.frames {
+                        let _ = write!(details, "\n    at {}", frame);
+                    }
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure type=\"{}\" message=\"{}\">{}</failure>\n    </testcase>",
+                        xml_escape(&crash.outcome.name()),
+                        xml_escape(&check.message(crash)),
+                        xml_escape(&details)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Serialize the failed checks as a SARIF 2.1.0 log.
+pub fn sarif(checks: &[Check]) -> String {
+    let results = checks.iter().filter_map(|check| {
+        let crash = check.failure.as_ref()?;
+        let locations = check.location().map(|(file, line, column)| {
+            let mut region = Vec::new();
+            if let Some(line) = line {
+                region.push(("startLine", line.to_string()));
+            }
+            if let Some(column) = column {
+                region.push(("startColumn", column.to_string()));
+            }
+            json::object([(
+                "physicalLocation",
+                json::object([
+                    ("artifactLocation", json::object([("uri", json::string(file))])),
+                    ("region", json::object(region)),
+                ]),
+            )])
+        });
+        Some(json::object([
+            ("ruleId", json::string(&crash.outcome.name())),
+            ("level", json::string("error")),
+            (
+                "message",
+                json::object([("text", json::string(&check.message(crash)))]),
+            ),
+            ("locations", json::array(locations)),
+        ]))
+    });
+
+    let driver = json::object([
+        ("name", json::string("cargo-hfuzz")),
+        ("version", json::string(env!("CARGO_PKG_VERSION"))),
+        (
+            "informationUri",
+            json::string("https://github.com/rust-fuzz/honggfuzz-rs"),
+        ),
+    ]);
+    json::object([
+        (
+            "$schema",
+            json::string("https://json.schemastore.org/sarif-2.1.0.json"),
+        ),
+        ("version", json::string("2.1.0")),
+        (
+            "runs",
+            json::array([json::object([
+                ("tool", json::object([("driver", driver)])),
+                ("results", json::array(results)),
+            ])]),
+        ),
+    ])
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // control characters are not allowed in XML 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => out.push('?'),
+            c => out.push(c),
+        }
+    }
+    out
+}
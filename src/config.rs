@@ -0,0 +1,456 @@
+//! Per-target build and run settings for `cargo hfuzz`.
+//!
+//! Settings are read from the `[package.metadata.honggfuzz]` section of
+//! `Cargo.toml` and then from an optional `Hfuzz.toml` file next to it, which
+//! takes precedence. Both accept the same keys, at the top level for every
+//! target and in a `targets.<NAME>` table for a single target:
+//!
+//! ```toml
+//! [package.metadata.honggfuzz]
+//! threads = 4
+//! rustflags = ["-C", "target-feature=+aes"]
+//!
+//! [package.metadata.honggfuzz.targets.example]
+//! timeout = 10
+//! dictionary = "fuzz/example.dict"
+//! input = "fuzz/corpus/example"
+//! run_args = ["--exit_upon_crash"]
+//! # only these crates get coverage instrumentation, `*` matches any suffix
+//! instrument = ["example", "example_parser*"]
+//! # sanitizer-coverage features, `-`/`+` remove from or add to the defaults
+//! coverage = ["-trace-geps", "+inline-8bit-counters", "+pc-table"]
+//! # also instrument the C/C++ code compiled by build scripts (`-sys` crates)
+//! instrument_c = true
+//! ```
+//!
+//! The `HFUZZ_WORKSPACE`, `HFUZZ_INPUT`, `HFUZZ_RUN_ARGS`, `HFUZZ_BUILD_ARGS`
+//! and `RUSTFLAGS` environment variables still take precedence over these
+//! settings.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the optional configuration file at the crate root.
+pub const CONFIG_FILENAME: &str = "Hfuzz.toml";
+
+/// Sanitizer-coverage features that can be given to `coverage`.
+///
+/// Each one is the `-sanitizer-coverage-<FEATURE>` LLVM option, except
+/// `indirect-calls` which selects coverage level 4 instead of 3.
+pub const COVERAGE_FEATURES: &[&str] = &[
+    "indirect-calls",
+    "trace-pc-guard",
+    "trace-pc",
+    "inline-8bit-counters",
+    "inline-bool-flag",
+    "pc-table",
+    "trace-compares",
+    "trace-divs",
+    "trace-geps",
+    "trace-loads",
+    "trace-stores",
+    "stack-depth",
+];
+
+/// Clang `-fsanitize-coverage=` flag matching the sanitizer-coverage `features`
+/// of the Rust code, for C and C++ code compiled by build scripts.
+///
+/// ```
+/// use honggfuzz::config::{clang_coverage_flag, default_coverage};
+///
+/// assert_eq!(
+///     clang_coverage_flag(&["indirect-calls".into(), "trace-pc-guard".into(), "trace-compares".into()]),
+///     "-fsanitize-coverage=edge,trace-pc-guard,trace-cmp"
+/// );
+/// ```
+pub fn clang_coverage_flag(features: &[String]) -> String {
+    let mut flag = String::from("-fsanitize-coverage=edge");
+    for feature in features {
+        let clang_feature = match feature.as_str() {
+            // clang always instruments indirect calls with edge coverage
+            "indirect-calls" => continue,
+            "trace-compares" => "trace-cmp",
+            "trace-divs" => "trace-div",
+            "trace-geps" => "trace-gep",
+            other => other,
+        };
+        flag.push(',');
+        flag.push_str(clang_feature);
+    }
+    flag
+}
+
+/// Sanitizer-coverage features used when `coverage` is not set.
+pub fn default_coverage() -> Vec<String> {
+    let mut features = vec![
+        "indirect-calls",
+        "trace-pc-guard",
+        "trace-divs",
+        "trace-geps",
+        "stack-depth",
+    ];
+    // trace-compares doesn't work on macOS without a sanitizer
+    if cfg!(not(target_os = "macos")) {
+        features.push("trace-compares");
+    }
+    features.into_iter().map(String::from).collect()
+}
+
+/// Resolve a `coverage` setting into the list of sanitizer-coverage features.
+///
+/// Items prefixed with `-` or `+` remove from or add to the defaults, any
+/// other item replaces the defaults altogether.
+///
+/// ```
+/// use honggfuzz::config::coverage_features;
+///
+/// let features = coverage_features(&["-trace-geps".into(), "+pc-table".into()]).unwrap();
+/// assert!(!features.contains(&"trace-geps".to_string()));
+/// assert!(features.contains(&"trace-pc-guard".to_string()));
+/// assert!(features.contains(&"pc-table".to_string()));
+///
+/// let features = coverage_features(&["inline-8bit-counters".into(), "pc-table".into()]).unwrap();
+/// assert_eq!(features, ["inline-8bit-counters", "pc-table"]);
+///
+/// assert!(coverage_features(&["trace-everything".into()]).is_err());
+/// ```
+pub fn coverage_features(spec: &[String]) -> io::Result<Vec<String>> {
+    let replaces = spec
+        .iter()
+        .any(|item| !item.starts_with('-') && !item.starts_with('+'));
+    let mut features = if replaces {
+        Vec::new()
+    } else {
+        default_coverage()
+    };
+    for item in spec {
+        let (remove, name) = match item.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, item.strip_prefix('+').unwrap_or(item)),
+        };
+        if !COVERAGE_FEATURES.contains(&name) {
+            return Err(invalid(format!(
+                "unknown coverage feature `{}`, possible values are: {}",
+                name,
+                COVERAGE_FEATURES.join(", ")
+            )));
+        }
+        features.retain(|feature| feature != name);
+        if !remove {
+            features.push(name.to_string());
+        }
+    }
+    Ok(features)
+}
+
+/// Settings of a single fuzz target.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetConfig {
+    /// Timeout of a single run in seconds (honggfuzz `-t`).
+    pub timeout: Option<u64>,
+    /// Number of fuzzing threads (honggfuzz `-n`).
+    pub threads: Option<u64>,
+    /// Dictionary file (honggfuzz `-w`).
+    pub dictionary: Option<PathBuf>,
+    /// Input corpus directory (honggfuzz `-f`), like `HFUZZ_INPUT`.
+    pub input: Option<PathBuf>,
+    /// Honggfuzz working directory, like `HFUZZ_WORKSPACE`.
+    pub workspace: Option<PathBuf>,
+    /// Extra flags given to `rustc`, like `RUSTFLAGS`.
+    pub rustflags: Vec<String>,
+    /// Extra arguments given to `cargo build`, like `HFUZZ_BUILD_ARGS`.
+    pub build_args: Vec<String>,
+    /// Extra arguments given to `honggfuzz`, like `HFUZZ_RUN_ARGS`.
+    pub run_args: Vec<String>,
+    /// Crates getting coverage instrumentation, all of them when empty.
+    pub instrument: Vec<String>,
+    /// Crates never getting coverage instrumentation.
+    pub no_instrument: Vec<String>,
+    /// Sanitizer-coverage features, see [`coverage_features`].
+    pub coverage: Option<Vec<String>>,
+    /// Whether the C and C++ code compiled by build scripts is instrumented too.
+    pub instrument_c: Option<bool>,
+}
+
+impl TargetConfig {
+    /// Overlay `other` on top of `self`: values set in `other` win, lists are appended.
+    pub fn merge(&mut self, other: &TargetConfig) {
+        if other.timeout.is_some() {
+            self.timeout = other.timeout;
+        }
+        if other.threads.is_some() {
+            self.threads = other.threads;
+        }
+        if other.dictionary.is_some() {
+            self.dictionary.clone_from(&other.dictionary);
+        }
+        if other.input.is_some() {
+            self.input.clone_from(&other.input);
+        }
+        if other.workspace.is_some() {
+            self.workspace.clone_from(&other.workspace);
+        }
+        self.rustflags.extend(other.rustflags.iter().cloned());
+        self.build_args.extend(other.build_args.iter().cloned());
+        self.run_args.extend(other.run_args.iter().cloned());
+        self.instrument.extend(other.instrument.iter().cloned());
+        self.no_instrument.extend(other.no_instrument.iter().cloned());
+        if other.coverage.is_some() {
+            self.coverage.clone_from(&other.coverage);
+        }
+        if other.instrument_c.is_some() {
+            self.instrument_c = other.instrument_c;
+        }
+    }
+
+    /// The settings changing how the target is built, the others left to their
+    /// defaults. Targets with the same build settings can be built together.
+    ///
+    /// ```
+    /// use honggfuzz::config::TargetConfig;
+    ///
+    /// let config = TargetConfig {
+    ///     timeout: Some(10),
+    ///     rustflags: vec!["-C".into(), "target-feature=+aes".into()],
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(config.build_settings().timeout, None);
+    /// assert_eq!(config.build_settings().rustflags, config.rustflags);
+    /// ```
+    pub fn build_settings(&self) -> TargetConfig {
+        TargetConfig {
+            rustflags: self.rustflags.clone(),
+            build_args: self.build_args.clone(),
+            instrument: self.instrument.clone(),
+            no_instrument: self.no_instrument.clone(),
+            coverage: self.coverage.clone(),
+            instrument_c: self.instrument_c,
+            ..TargetConfig::default()
+        }
+    }
+
+    /// Whether coverage instrumentation is limited to some crates.
+    pub fn filters_instrumentation(&self) -> bool {
+        !self.instrument.is_empty() || !self.no_instrument.is_empty()
+    }
+
+    /// Whether the crate named `crate_name` gets coverage instrumentation.
+    ///
+    /// Patterns match crate names with `-` and `_` treated alike, and a
+    /// trailing `*` matches any suffix.
+    ///
+    /// ```
+    /// use honggfuzz::config::TargetConfig;
+    ///
+    /// let config = TargetConfig {
+    ///     instrument: vec!["example".into(), "example-parser*".into()],
+    ///     no_instrument: vec!["example_parser_tests".into()],
+    ///     ..Default::default()
+    /// };
+    /// assert!(config.instruments("example"));
+    /// assert!(config.instruments("example_parser_json"));
+    /// assert!(!config.instruments("example_parser_tests"));
+    /// assert!(!config.instruments("serde"));
+    ///
+    /// assert!(TargetConfig::default().instruments("serde"));
+    /// ```
+    pub fn instruments(&self, crate_name: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| crate_name_matches(pattern, crate_name))
+        };
+        (self.instrument.is_empty() || matches(&self.instrument)) && !matches(&self.no_instrument)
+    }
+
+    fn from_table(table: &toml::Table, context: &str) -> io::Result<TargetConfig> {
+        let mut config = TargetConfig::default();
+        for (key, value) in table {
+            let context = format!("{}.{}", context, key);
+            match key.as_str() {
+                "timeout" => config.timeout = Some(integer(value, &context)?),
+                "threads" => config.threads = Some(integer(value, &context)?),
+                "dictionary" => config.dictionary = Some(string(value, &context)?.into()),
+                "input" => config.input = Some(string(value, &context)?.into()),
+                "workspace" => config.workspace = Some(string(value, &context)?.into()),
+                "rustflags" => config.rustflags = strings(value, &context)?,
+                "build_args" => config.build_args = strings(value, &context)?,
+                "run_args" => config.run_args = strings(value, &context)?,
+                "instrument" => config.instrument = strings(value, &context)?,
+                "no_instrument" => config.no_instrument = strings(value, &context)?,
+                "instrument_c" => config.instrument_c = Some(boolean(value, &context)?),
+                "coverage" => {
+                    let spec = strings(value, &context)?;
+                    coverage_features(&spec).map_err(|e| invalid(format!("`{}`: {}", context, e)))?;
+                    config.coverage = Some(spec);
+                }
+                "targets" => {} // handled by `Config::from_table`
+                _ => return Err(invalid(format!("unknown key `{}`", context))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Settings of every fuzz target of a crate.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Settings shared by every target.
+    pub defaults: TargetConfig,
+    /// Settings of specific targets, overriding `defaults`.
+    pub targets: BTreeMap<String, TargetConfig>,
+}
+
+impl Config {
+    /// Load the settings of the crate at `crate_root`, from its `Cargo.toml` and `Hfuzz.toml`.
+    pub fn load(crate_root: &Path) -> io::Result<Config> {
+        let mut config = Config::default();
+
+        let manifest_path = crate_root.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path)?;
+        config.merge(&Config::parse_manifest(&manifest).map_err(|e| in_file(&manifest_path, e))?);
+
+        let config_path = crate_root.join(CONFIG_FILENAME);
+        if config_path.is_file() {
+            let text = fs::read_to_string(&config_path)?;
+            config.merge(&Config::parse(&text).map_err(|e| in_file(&config_path, e))?);
+        }
+
+        Ok(config)
+    }
+
+    /// Parse the content of a `Hfuzz.toml` file.
+    ///
+    /// ```
+    /// use honggfuzz::config::Config;
+    ///
+    /// let config = Config::parse(r#"
+    ///     threads = 4
+    ///     run_args = ["-v"]
+    ///
+    ///     [targets.example]
+    ///     timeout = 10
+    ///     run_args = ["--exit_upon_crash"]
+    /// "#).unwrap();
+    ///
+    /// let example = config.target("example");
+    /// assert_eq!(example.threads, Some(4));
+    /// assert_eq!(example.timeout, Some(10));
+    /// assert_eq!(example.run_args, ["-v", "--exit_upon_crash"]);
+    /// assert_eq!(config.target("other").timeout, None);
+    ///
+    /// assert!(Config::parse("timeout = \"10\"").is_err());
+    /// assert!(Config::parse("thread = 4").is_err());
+    /// ```
+    pub fn parse(text: &str) -> io::Result<Config> {
+        let table: toml::Table = text.parse().map_err(invalid)?;
+        Config::from_table(&table, "honggfuzz")
+    }
+
+    /// Parse the `[package.metadata.honggfuzz]` section of a `Cargo.toml` file.
+    pub fn parse_manifest(text: &str) -> io::Result<Config> {
+        let manifest: toml::Table = text.parse().map_err(invalid)?;
+        match manifest
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("honggfuzz"))
+        {
+            Some(section) => Config::from_table(
+                table(section, "package.metadata.honggfuzz")?,
+                "package.metadata.honggfuzz",
+            ),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Settings of `target`, that is the defaults overridden by the target specific settings.
+    pub fn target(&self, target: &str) -> TargetConfig {
+        let mut config = self.defaults.clone();
+        if let Some(target_config) = self.targets.get(target) {
+            config.merge(target_config);
+        }
+        config
+    }
+
+    /// Overlay `other` on top of `self`.
+    pub fn merge(&mut self, other: &Config) {
+        self.defaults.merge(&other.defaults);
+        for (target, config) in &other.targets {
+            self.targets.entry(target.clone()).or_default().merge(config);
+        }
+    }
+
+    fn from_table(root: &toml::Table, context: &str) -> io::Result<Config> {
+        let mut config = Config {
+            defaults: TargetConfig::from_table(root, context)?,
+            targets: BTreeMap::new(),
+        };
+        if let Some(targets) = root.get("targets") {
+            let context = format!("{}.targets", context);
+            for (target, value) in table(targets, &context)? {
+                let context = format!("{}.{}", context, target);
+                let target_config = TargetConfig::from_table(table(value, &context)?, &context)?;
+                config.targets.insert(target.clone(), target_config);
+            }
+        }
+        Ok(config)
+    }
+}
+
+// rustc sees `my-crate` as `my_crate`
+fn crate_name_matches(pattern: &str, crate_name: &str) -> bool {
+    let pattern = pattern.replace('-', "_");
+    let crate_name = crate_name.replace('-', "_");
+    match pattern.strip_suffix('*') {
+        Some(prefix) => crate_name.starts_with(prefix),
+        None => crate_name == pattern,
+    }
+}
+
+fn invalid<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn in_file(path: &Path, e: io::Error) -> io::Error {
+    invalid(format!("{}: {}", path.display(), e))
+}
+
+fn table<'a>(value: &'a toml::Value, context: &str) -> io::Result<&'a toml::Table> {
+    value
+        .as_table()
+        .ok_or_else(|| invalid(format!("`{}` should be a table", context)))
+}
+
+fn integer(value: &toml::Value, context: &str) -> io::Result<u64> {
+    value
+        .as_integer()
+        .and_then(|i| u64::try_from(i).ok())
+        .ok_or_else(|| invalid(format!("`{}` should be a positive integer", context)))
+}
+
+fn boolean(value: &toml::Value, context: &str) -> io::Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| invalid(format!("`{}` should be a boolean", context)))
+}
+
+fn string(value: &toml::Value, context: &str) -> io::Result<String> {
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| invalid(format!("`{}` should be a string", context)))
+}
+
+fn strings(value: &toml::Value, context: &str) -> io::Result<Vec<String>> {
+    value
+        .as_array()
+        .and_then(|array| {
+            array
+                .iter()
+                .map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .ok_or_else(|| invalid(format!("`{}` should be an array of strings", context)))
+}
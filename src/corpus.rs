@@ -0,0 +1,185 @@
+//! Moving corpora between honggfuzz and other fuzzing engines.
+//!
+//! Used by `cargo hfuzz import` and `cargo hfuzz export` to copy inputs from
+//! and to the directory layouts of cargo-fuzz (`fuzz/corpus/<TARGET>`) and
+//! AFL (`<OUTPUT>/queue` or `<OUTPUT>/<INSTANCE>/queue`), skipping inputs
+//! whose content is already present.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Fuzzing engine layouts supported by import and export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    /// cargo-fuzz (libFuzzer), corpus in `<FUZZ_DIR>/corpus/<TARGET>`.
+    CargoFuzz,
+    /// AFL and AFL++, inputs in `<OUTPUT>/queue` or `<OUTPUT>/<INSTANCE>/queue`.
+    Afl,
+}
+
+impl Engine {
+    /// Parse an engine name, `cargo-fuzz` or `afl`.
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match name {
+            "cargo-fuzz" | "libfuzzer" => Some(Engine::CargoFuzz),
+            "afl" => Some(Engine::Afl),
+            _ => None,
+        }
+    }
+
+    /// Directories holding the inputs of `target` in the layout rooted at `path`.
+    ///
+    /// Falls back to `path` itself when it doesn't look like the engine's layout,
+    /// so a corpus directory can also be given directly.
+    pub fn input_dirs(&self, path: &Path, target: &str) -> Vec<PathBuf> {
+        let dirs = match *self {
+            // `fuzz/corpus` without the target's directory means there is nothing to import
+            Engine::CargoFuzz if path.join("corpus").is_dir() => {
+                return vec![path.join("corpus").join(target)]
+                    .into_iter()
+                    .filter(|dir| dir.is_dir())
+                    .collect();
+            }
+            Engine::CargoFuzz => vec![],
+            Engine::Afl => {
+                // a single instance, or one directory per instance (`-M`/`-S`, AFL++'s `default`)
+                let mut dirs = vec![path.join("queue")];
+                let mut instances: Vec<_> = fs::read_dir(path)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path().join("queue")))
+                    .collect();
+                instances.sort();
+                dirs.extend(instances);
+                dirs
+            }
+        };
+        let dirs: Vec<_> = dirs.into_iter().filter(|dir| dir.is_dir()).collect();
+        if dirs.is_empty() {
+            vec![path.to_path_buf()]
+        } else {
+            dirs
+        }
+    }
+
+    /// Directory where the corpus of `target` is exported in the layout rooted at `path`.
+    pub fn output_dir(&self, path: &Path, target: &str) -> PathBuf {
+        match *self {
+            Engine::CargoFuzz => path.join("corpus").join(target),
+            // AFL takes its seeds from any directory given with `-i`
+            Engine::Afl => path.to_path_buf(),
+        }
+    }
+}
+
+/// Dictionaries (`*.dict`) found directly in `dir`.
+pub fn dictionaries(dir: &Path) -> Vec<PathBuf> {
+    let mut dicts: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "dict"))
+        .collect();
+    dicts.sort();
+    dicts
+}
+
+/// Number of inputs copied and skipped by [`copy_unique`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyStats {
+    /// Inputs copied to the destination.
+    pub copied: usize,
+    /// Inputs skipped because their content was already in the destination.
+    pub duplicates: usize,
+}
+
+/// Copy the inputs found in `sources` to `dest`, skipping the ones whose content
+/// is already in `dest`.
+///
+/// Copied inputs are named after the 64-bit FNV-1a hash of their content, which
+/// doesn't depend on the Rust version. With `link`, inputs
+/// are hard-linked instead of copied when possible. Hidden files and
+/// subdirectories, such as AFL's `.state`, are ignored.
+///
+/// ```
+/// use honggfuzz::corpus::copy_unique;
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join(format!("honggfuzz-corpus-doctest-{}", std::process::id()));
+/// let (source, dest) = (dir.join("source"), dir.join("dest"));
+/// fs::create_dir_all(&source).unwrap();
+/// fs::write(source.join("a"), "hey").unwrap();
+/// fs::write(source.join("b"), "hey").unwrap();
+/// fs::write(source.join("c"), "ho").unwrap();
+///
+/// let stats = copy_unique(&[source.clone()], &dest, false).unwrap();
+/// assert_eq!((stats.copied, stats.duplicates), (2, 1));
+/// assert_eq!(fs::read(dest.join("334a25192fe3767d")).unwrap(), b"hey");
+///
+/// // importing the same inputs again doesn't copy anything
+/// let stats = copy_unique(&[source], &dest, false).unwrap();
+/// assert_eq!((stats.copied, stats.duplicates), (0, 3));
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn copy_unique(sources: &[PathBuf], dest: &Path, link: bool) -> io::Result<CopyStats> {
+    fs::create_dir_all(dest)?;
+
+    // content hash -> files with that hash, to compare contents on collisions
+    let mut known = HashMap::<u64, Vec<PathBuf>>::new();
+    for path in inputs(dest)? {
+        known.entry(hash(&fs::read(&path)?)).or_default().push(path);
+    }
+
+    let mut stats = CopyStats::default();
+    for source in sources {
+        for path in inputs(source)? {
+            let content = fs::read(&path)?;
+            let hash = hash(&content);
+            let same_hash = known.entry(hash).or_default();
+            if same_hash
+                .iter()
+                .any(|other| fs::read(other).is_ok_and(|other| other == content))
+            {
+                stats.duplicates += 1;
+                continue;
+            }
+
+            let mut copy = dest.join(format!("{:016x}", hash));
+            if copy.exists() {
+                // a real collision, keep both
+                copy = dest.join(format!("{:016x}.{}", hash, same_hash.len()));
+            }
+            if !link || fs::hard_link(&path, &copy).is_err() {
+                fs::write(&copy, &content)?;
+            }
+            same_hash.push(copy);
+            stats.copied += 1;
+        }
+    }
+    Ok(stats)
+}
+
+fn inputs(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if path.is_file() && !hidden {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+// FNV-1a, stable across Rust versions unlike `DefaultHasher`, so that the
+// names of inputs already copied don't change with the toolchain
+fn hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
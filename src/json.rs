@@ -0,0 +1,59 @@
+// Minimal JSON serialization helpers, so that `cargo hfuzz` can emit
+// machine-readable output without pulling `serde` into every fuzzed crate.
+
+use std::fmt::Write;
+
+/// Quote and escape `s` as a JSON string literal.
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Serialize an optional string as a JSON string or `null`.
+pub(crate) fn opt_string(s: Option<&str>) -> String {
+    s.map_or_else(|| "null".to_string(), string)
+}
+
+/// Serialize a list of strings as a JSON array.
+pub(crate) fn string_array<I, S>(items: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    array(items.into_iter().map(|s| string(s.as_ref())))
+}
+
+/// Join already serialized JSON values into an array.
+pub(crate) fn array<I>(items: I) -> String
+where
+    I: IntoIterator<Item = String>,
+{
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// Join already serialized `(key, value)` pairs into an object.
+pub(crate) fn object<'a, I>(fields: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, String)>,
+{
+    let fields: Vec<_> = fields
+        .into_iter()
+        .map(|(k, v)| format!("{}:{}", string(k), v))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
@@ -12,14 +12,14 @@
 //! * __Rust__: stable, beta, nightly
 //! * __OS__: GNU/Linux, macOS, FreeBSD, NetBSD, Android, WSL (Windows Subsystem for Linux)
 //! * __Arch__: x86_64, x86, arm64-v8a, armeabi-v7a, armeabi
-//! * __Sanitizer__: none, address, thread, leak
+//! * __Sanitizer__: none, address, thread, leak, memory (nightly only, rebuilds the standard library)
 //!
 //! ## Dependencies
 //!
 //! ### Linux
 //!
 //! * C compiler: `cc`
-//! * GNU Make: `make`
+//! * GNU Make: `make` (to build the `honggfuzz` command, the runtime linked in the fuzzed binaries is built with the [`cc`](https://crates.io/crates/cc) crate and follows its `CC`, `CFLAGS` ... variables)
 //! * GNU Binutils development files for the BFD library: `libbfd.h`
 //! * libunwind development files: `libunwind.h`
 //! * liblzma development files
@@ -30,6 +30,15 @@
 //! sudo apt install build-essential binutils-dev libunwind-dev
 //! ```
 //!
+//! Without libbfd, libunwind and liblzma, the `minimal-driver` feature builds the `honggfuzz` command without stack unwinding:
+//!
+//! ```toml
+//! [dependencies]
+//! honggfuzz = { version = "0.5", features = ["minimal-driver"] }
+//! ```
+//!
+//! Crashes are then told apart by their program counter only, so `cargo hfuzz run` saves all of them (`--save_all`) and `cargo hfuzz triage` groups them by the stack of their panic. `cargo hfuzz version` tells which driver is built.
+//!
 //! ## How to use this crate
 //!
 //! Install honggfuzz commands to build with instrumentation and fuzz
@@ -45,6 +54,9 @@
 //! [dependencies]
 //! honggfuzz = "0.5"
 //! ```
+//! 
+//! The default `cli` feature provides the `cargo hfuzz` command and the modules driving honggfuzz (`campaign`, `triage` ...).
+//! A fuzzed crate only needs the `fuzz!` macro and can leave them and their dependencies out with `honggfuzz = { version = "0.5", default-features = false, features = ["arbitrary"] }`.
 //!
 //! Create a target to fuzz
 //!
@@ -80,6 +92,36 @@
 //! ```sh
 //! # builds with fuzzing instrumentation and then fuzz the "example" target
 //! cargo hfuzz run example
+//! # fuzz targets can also be examples, or tests and benches with `harness = false`, so that library crates don't need to ship binaries
+//! cargo hfuzz run --example example
+//! ```
+//!
+//! Keep cargo-hfuzz around honggfuzz to know what a campaign found
+//!
+//! ```sh
+//! # runs honggfuzz as a child, forwarding signals to it, then prints the new inputs, the coverage and the new crashes;
+//! # exits with 1 if new crashes were found, 2 if honggfuzz failed, 0 otherwise
+//! cargo hfuzz run --supervise example
+//! # also runs actions on the new crashes: "report" prints what honggfuzz reported about them, "triage" replays and groups them,
+//! # and --on-crash-exec runs a shell command for each of them with its path in CARGO_HONGGFUZZ_CRASH_FILENAME
+//! cargo hfuzz run --on-crash report --on-crash triage --on-crash-exec 'cp "$CARGO_HONGGFUZZ_CRASH_FILENAME" crashes/' example
+//! ```
+//!
+//! Fuzz every binary target of the crate, one after another or several at the same time
+//!
+//! ```sh
+//! # builds all targets with their own settings, then fuzzes them 2 at a time for 10 minutes each, sharing the cores between them
+//! cargo hfuzz run-all --time 600 --jobs 2
+//! # or only the given targets, selected like for `cargo hfuzz run`
+//! cargo hfuzz run-all --time 600 example --example example
+//! ```
+//!
+//! In continuous integration, replay every saved crash and corpus input as a regression test, then fuzz each target for a short time.
+//! Inputs running longer than the target's `timeout` setting (10 seconds by default) fail as timeouts.
+//! The command fails if anything crashes or honggfuzz fails, and can write JUnit XML and SARIF reports pointing at the panic locations
+//!
+//! ```sh
+//! cargo hfuzz ci --time 60 --junit hfuzz-junit.xml --sarif hfuzz.sarif
 //! ```
 //!
 //! Once you got a crash, replay it easily in a debug environment
@@ -87,6 +129,57 @@
 //! ```sh
 //! # builds the target in debug mode and replays automatically the crash in gdb
 //! cargo hfuzz run-debug example fuzzing_workspace/*.fuzz
+//! # records the replay with rr and opens the recording on the panic, to step backwards from it
+//! cargo hfuzz run-debug --record example fuzzing_workspace/*.fuzz
+//! # without interaction, prints the backtrace and the locals of the top frames on a panic or a fatal signal (SIGSEGV, SIGABRT ...)
+//! # and saves them to CRASH_FILENAME.backtrace.txt, or to the file given with --batch-output
+//! cargo hfuzz run-debug --batch example fuzzing_workspace/*.fuzz
+//! ```
+//!
+//! After a long campaign, many crash files are usually the same bug. Group them by panic location and stack frames
+//!
+//! ```sh
+//! # replays every crash of the "example" target in debug mode, groups them and shows the smallest reproducer of each group
+//! cargo hfuzz triage example
+//! # same, as JSON, using the top 5 frames to tell crashes apart
+//! cargo hfuzz triage example --json --frames 5
+//! ```
+//!
+//! Honggfuzz also describes every crash it saves in `HONGGFUZZ.REPORT.TXT`, you can get it as JSON
+//!
+//! ```sh
+//! # parses hfuzz_workspace/example/HONGGFUZZ.REPORT.TXT
+//! cargo hfuzz report example
+//! ```
+//!
+//! Follow a running campaign from another terminal
+//!
+//! ```sh
+//! # asks honggfuzz to write its statistics in hfuzz_workspace/example/HONGGFUZZ.STATS.CSV
+//! cargo hfuzz run --stats-file example
+//! # shows execs/sec, coverage, crashes and the time since the last new coverage, refreshed every second
+//! cargo hfuzz stats example --watch
+//! ```
+//!
+//! Reuse the corpus and dictionaries of another fuzzer, or share yours with it. Inputs already present are skipped
+//!
+//! ```sh
+//! # copies fuzz/corpus/example and fuzz/*.dict from a cargo-fuzz project into hfuzz_workspace/example
+//! cargo hfuzz import --from cargo-fuzz fuzz example
+//! # copies the queue of every AFL instance of the output directory, with hard links when possible
+//! cargo hfuzz import --from afl afl_out example --link
+//! # copies hfuzz_workspace/example/input to fuzz/corpus/example
+//! cargo hfuzz export --to cargo-fuzz fuzz example
+//! ```
+//!
+//! Hand a crash over to someone who doesn't have your project: a bundle holds the input, the debug build of the target, its panic output, the rustc version, the rustflags and the git commit.
+//! The crash is replayed with the target's `timeout` setting (10 seconds by default) when bundling and unbundling, a hang is bundled as a timeout
+//!
+//! ```sh
+//! # writes hfuzz_workspace/example/bundles/CRASH_FILENAME.tar.gz
+//! cargo hfuzz bundle example hfuzz_workspace/example/CRASH_FILENAME
+//! # extracts the bundle next to it and replays the crash, "--debug" replays it in the debugger instead
+//! cargo hfuzz unbundle CRASH_FILENAME.tar.gz
 //! ```
 //!
 //! You can also build and run your project without compile-time software instrumentation (LLVM's SanCov passes)
@@ -98,6 +191,22 @@
 //! HFUZZ_RUN_ARGS="--linux_perf_ipt_block --linux_perf_instr --linux_perf_branch" cargo hfuzz run-no-instr example
 //! ```
 //!
+//! You can build for another target triple, for example another libc or a 32-bit architecture.
+//! The honggfuzz runtime is then compiled for that triple by the [`cc`](https://crates.io/crates/cc) crate, with the C compiler and flags from `CC_<triple>`, `CFLAGS_<triple>` ... or `TARGET_CC`, `TARGET_CFLAGS` ..., and otherwise the defaults of `cc` for the triple
+//!
+//! ```sh
+//! cargo hfuzz run --target x86_64-unknown-linux-musl example
+//! cargo hfuzz build --target i686-unknown-linux-gnu
+//! ```
+//!
+//! Targets are built with the `release` profile to fuzz and the `dev` profile to debug, pick another cargo profile with `--profile`
+//!
+//! ```sh
+//! # builds with [profile.fuzz] and runs hfuzz_target/<triple>/fuzz/example
+//! cargo hfuzz run --profile fuzz example
+//! cargo hfuzz run-debug --profile fuzz-debug example hfuzz_workspace/*/*.fuzz
+//! ```
+//!
 //! Clean
 //!
 //! ```sh
@@ -105,12 +214,64 @@
 //! cargo hfuzz clean
 //! ```
 //!
+//! Doctor
+//!
+//! ```sh
+//! # checks the C compiler, make, the headers of libbfd, libunwind and liblzma, the nightly toolchain, the debugger,
+//! # perf_event_paranoid, core_pattern and that cargo-hfuzz matches the honggfuzz version of Cargo.lock, and tells how to fix them
+//! cargo hfuzz doctor
+//! ```
+//!
 //! Version
 //!
 //! ```sh
 //! cargo hfuzz version
 //! ```
 //!
+//! ### Per-target settings
+//!
+//! Build and run settings can be written in a `[package.metadata.honggfuzz]` section of `Cargo.toml`, or in a `Hfuzz.toml` file next to it (which takes precedence).
+//! Top-level keys apply to every target and `targets.<NAME>` tables to a single target:
+//!
+//! ```toml
+//! [package.metadata.honggfuzz]
+//! threads = 4                                  # honggfuzz -n
+//! rustflags = ["-C", "target-feature=+aes"]    # added to RUSTFLAGS
+//!
+//! [package.metadata.honggfuzz.targets.example]
+//! timeout = 10                                 # honggfuzz -t, in seconds
+//! dictionary = "fuzz/example.dict"             # honggfuzz -w
+//! input = "fuzz/corpus/example"                # like HFUZZ_INPUT
+//! workspace = "fuzz/workspace"                 # like HFUZZ_WORKSPACE
+//! build_args = ["--features", "fuzz"]          # like HFUZZ_BUILD_ARGS
+//! run_args = ["--exit_upon_crash"]             # like HFUZZ_RUN_ARGS
+//! instrument = ["example", "example-core*"]    # only these crates get coverage instrumentation
+//! no_instrument = ["example-core-tests"]       # and never these ones
+//! coverage = ["-trace-geps", "+pc-table"]      # sanitizer-coverage features, see below
+//! instrument_c = true                          # instrument C/C++ code of build scripts, see below
+//! ```
+//!
+//! By default every crate, dependencies included, is instrumented for coverage.
+//! With `instrument` or `no_instrument`, crates are matched by name (a trailing `*` matches any suffix) and cargo-hfuzz, acting as `RUSTC_WRAPPER`, only adds the instrumentation flags to the matching ones.
+//! An existing `RUSTC_WRAPPER`, such as `sccache`, is still called.
+//!
+//! Instrumented builds use the `indirect-calls`, `trace-pc-guard`, `trace-divs`, `trace-geps`, `stack-depth` and `trace-compares` (except on macOS) sanitizer-coverage features.
+//! `coverage`, or `--coverage` on the command line, removes (`-trace-geps`) or adds (`+inline-8bit-counters`) features, or replaces them all when given without `-`/`+`.
+//! The features used are written to `hfuzz-coverage.txt` next to the built binaries.
+//!
+//! ```sh
+//! cargo hfuzz run --coverage=-trace-geps,+inline-8bit-counters,+pc-table example
+//! ```
+//!
+//! C and C++ code compiled by the build scripts of `-sys` crates (with the `cc` crate) gets no coverage feedback by default.
+//! With `instrument_c`, or `--instrument-c` on the command line, it is compiled by `clang`/`clang++` (unless `CC`/`CXX` are set) with the `-fsanitize-coverage` features matching the Rust ones. Build dependencies and proc-macros, which only run at build time, are not instrumented.
+//!
+//! ```sh
+//! cargo hfuzz run --instrument-c example
+//! ```
+//!
+//! The environment variables below take precedence over these settings.
+//!
 //! ### Environment variables
 //!
 //! #### `RUSTFLAGS`
@@ -124,10 +285,21 @@
 //! RUSTFLAGS="-Z sanitizer=address" cargo hfuzz run example
 //! ```
 //!
+//! The `--sanitizer address|thread|leak|memory` option of the build and run commands sets those flags for you, checks that the toolchain is nightly,
+//! builds in a separate `sanitizer-<NAME>` directory of the target directory and sets `ASAN_OPTIONS` and `TSAN_OPTIONS` to work with Rust code (see `campaign::SANITIZER_OPTIONS`):
+//!
+//! ```sh
+//! cargo +nightly hfuzz run --sanitizer address example
+//! ```
+//!
+//! With `--sanitizer memory`, the standard library is rebuilt with `-Zbuild-std` since MemorySanitizer needs every piece of code to be instrumented, this requires the `rust-src` component (`rustup component add rust-src --toolchain nightly`).
+//!
 //! #### `HFUZZ_BUILD_ARGS`
 //!
 //! You can use `HFUZZ_BUILD_ARGS` to send additional arguments to `cargo build`.
 //!
+//! A `--profile NAME` given there is used when `--profile` is not given on the command line.
+//!
 //! #### `HFUZZ_RUN_ARGS`
 //!
 //! You can use `HFUZZ_RUN_ARGS` to send additional arguments to `honggfuzz`.
@@ -148,6 +320,8 @@
 //!
 //! By default we use `rust-lldb` but you can change it to `rust-gdb`, `gdb`, `/usr/bin/lldb-7` ...
 //!
+//! With `rr`, or with `cargo hfuzz run-debug --record`, the crash is recorded with `rr record` in `$HFUZZ_WORKSPACE/{TARGET}/rr-trace` and then opened in `rr replay` stopped on `rust_panic`, so that you can step backwards from the panic with `reverse-next`, `reverse-continue` ...
+//!
 //! #### `CARGO_TARGET_DIR`
 //!
 //! Target compilation directory, defaults to `hfuzz_target` to not clash with `cargo build`'s default `target` directory.
@@ -160,6 +334,18 @@
 //!
 //! Honggfuzz input files (also called "corpus"), defaults to `$HFUZZ_WORKSPACE/{TARGET}/input`.
 //!
+//! #### `HFUZZ_HONGGFUZZ_DIR`
+//!
+//! Use an existing honggfuzz instead of building the vendored one: either an install prefix (with `bin/honggfuzz`, `lib/libhfuzz.a` and `lib/libhfcommon.a`) or a honggfuzz build directory.
+//! Its version must be the one of the vendored honggfuzz, as reported by `honggfuzz --version`.
+//!
+//! With the `system-honggfuzz` feature of the `honggfuzz` dependency, the `honggfuzz` found in `PATH` is used when `HFUZZ_HONGGFUZZ_DIR` is not set.
+//!
+//! #### `HFUZZ_CACHE_DIR`
+//!
+//! When set, the honggfuzz runtime (the `honggfuzz` command, `libhfuzz.a` and `libhfcommon.a`) is built once for every version of its sources, target, compiler and flags, and shared by all projects and target directories in `$HFUZZ_CACHE_DIR/<hash>`, for instance with `HFUZZ_CACHE_DIR=~/.cache/honggfuzz-rs`.
+//! Unset or empty by default, the runtime is then built in the target directory only, as sandboxed builds can't write anywhere else.
+//!
 //! ## Conditional compilation
 //!
 //! Sometimes, it is necessary to make some specific adaptation to your code to yield a better fuzzing efficiency.
@@ -205,6 +391,27 @@
 //!
 //! This crate was inspired by those projects!
 
+// Everything driving honggfuzz rather than running inside the fuzzed binary is
+// part of the `cli` feature, so that fuzzed crates can build without it.
+#[cfg(feature = "cli")]
+pub mod bundle;
+#[cfg(feature = "cli")]
+pub mod campaign;
+#[cfg(feature = "cli")]
+pub mod ci;
+#[cfg(feature = "cli")]
+pub mod config;
+#[cfg(feature = "cli")]
+pub mod corpus;
+#[cfg(feature = "cli")]
+mod json;
+#[cfg(feature = "cli")]
+pub mod report;
+#[cfg(feature = "cli")]
+pub mod stats;
+#[cfg(feature = "cli")]
+pub mod triage;
+
 /// Re-export of arbitrary crate used to generate structured inputs
 #[cfg(feature = "arbitrary")]
 pub use arbitrary;
@@ -214,6 +421,12 @@ unsafe extern "C" {
     fn HF_ITER(buf_ptr: *mut *const u8, len_ptr: *mut usize);
 }
 
+// provided by the MemorySanitizer runtime, `--cfg fuzzing_msan` is set by `cargo hfuzz --sanitizer memory`
+#[cfg(all(fuzzing, not(fuzzing_debug), fuzzing_msan))]
+unsafe extern "C" {
+    fn __msan_unpoison(addr: *const std::ffi::c_void, size: usize);
+}
+
 /// Fuzz a closure by passing it a `&[u8]`
 ///
 /// This slice contains a "random" quantity of "random" data.
@@ -279,6 +492,16 @@ where
 
     unsafe {
         HF_ITER(buf_ptr.as_mut_ptr(), len_ptr.as_mut_ptr());
+
+        // libhfuzz is not instrumented by MemorySanitizer, so whatever it writes
+        // still looks uninitialized and would be reported as soon as it is read
+        #[cfg(fuzzing_msan)]
+        {
+            __msan_unpoison(buf_ptr.as_ptr().cast(), std::mem::size_of::<*const u8>());
+            __msan_unpoison(len_ptr.as_ptr().cast(), std::mem::size_of::<usize>());
+            __msan_unpoison(buf_ptr.assume_init().cast(), len_ptr.assume_init());
+        }
+
         buf = ::std::slice::from_raw_parts(buf_ptr.assume_init(), len_ptr.assume_init());
     }
 
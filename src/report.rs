@@ -0,0 +1,264 @@
+//! Parser for the `HONGGFUZZ.REPORT.TXT` file honggfuzz writes in its workspace.
+//!
+//! Every crash saved by honggfuzz appends a `CRASH:` section to this file. This
+//! module turns those free-text sections into [`CrashReport`] records that
+//! `cargo hfuzz report` serializes as JSON.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::json;
+
+/// Name of the report file in the honggfuzz workspace (`-W`).
+pub const REPORT_FILENAME: &str = "HONGGFUZZ.REPORT.TXT";
+
+/// A single `CRASH:` section of the report.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrashReport {
+    /// File the crashing input was saved to (`FUZZ_FNAME`).
+    pub crash_file: Option<String>,
+    /// File the input was mutated from (`ORIG_FNAME`), `[DYNAMIC]` for the dynamic corpus.
+    pub original_file: Option<String>,
+    /// Free-text description, usually empty.
+    pub description: Option<String>,
+    /// Pid of the crashed process.
+    pub pid: Option<u32>,
+    /// Signal name, e.g. `SIGSEGV`.
+    pub signal: Option<String>,
+    /// Signal number.
+    pub signal_number: Option<i32>,
+    /// Program counter at the time of the crash.
+    pub pc: Option<u64>,
+    /// Faulting address, as printed by honggfuzz.
+    pub fault_address: Option<String>,
+    /// Disassembled crashing instruction.
+    pub instruction: Option<String>,
+    /// Hash of the stack, used by honggfuzz to tell crashes apart.
+    pub stack_hash: Option<u64>,
+    /// Backtrace, innermost frame first.
+    pub backtrace: Vec<Frame>,
+}
+
+/// A frame of a [`CrashReport`] backtrace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    /// Program counter of the frame.
+    pub pc: u64,
+    /// Symbolized function name, `None` when honggfuzz printed `UNKNOWN`.
+    pub function: Option<String>,
+    /// Source file.
+    pub file: Option<String>,
+    /// Source line, `None` when unknown.
+    pub line: Option<u32>,
+    /// Executable or shared library containing the frame.
+    pub module: Option<String>,
+}
+
+/// Parse the content of a `HONGGFUZZ.REPORT.TXT` file.
+///
+/// Session headers (`TIME:`, `FUZZER ARGS:` ...) are skipped.
+///
+/// ```
+/// let report = "\
+/// =====================================================================
+/// TIME: 2024-01-01.12:00:00
+/// =====================================================================
+/// FUZZER ARGS:
+///  mutationsPerRun : 6
+/// CRASH:
+/// DESCRIPTION:
+/// ORIG_FNAME: [DYNAMIC]
+/// FUZZ_FNAME: hfuzz_workspace/example/SIGABRT.PC.7ffff7a42e97.STACK.18a8ce6a64.CODE.-6.ADDR.0.INSTR.mov____0x108(%rsp),%rax.fuzz
+/// PID: 4242
+/// SIGNAL: SIGABRT (6)
+/// PC: 0x7ffff7a42e97
+/// FAULT ADDRESS: 0x0
+/// INSTRUCTION: mov____0x108(%rsp),%rax
+/// STACK HASH: 00000018a8ce6a64
+/// STACK:
+///  <0x00007ffff7a42e97> [func:UNKNOWN file: line:0 module:/lib/x86_64-linux-gnu/libc.so.6]
+///  <0x000055555555e2b5> [func:example::main::{{closure}} file:src/main.rs line:36 module:hfuzz_target/x86_64-unknown-linux-gnu/release/example]
+/// =====================================================================
+/// ";
+/// let crashes = honggfuzz::report::parse(report);
+/// assert_eq!(crashes.len(), 1);
+/// let crash = &crashes[0];
+/// assert_eq!(crash.signal.as_deref(), Some("SIGABRT"));
+/// assert_eq!(crash.signal_number, Some(6));
+/// assert_eq!(crash.pc, Some(0x7ffff7a42e97));
+/// assert_eq!(crash.stack_hash, Some(0x18a8ce6a64));
+/// assert_eq!(crash.instruction.as_deref(), Some("mov____0x108(%rsp),%rax"));
+/// assert_eq!(crash.original_file.as_deref(), Some("[DYNAMIC]"));
+/// assert_eq!(crash.description, None);
+/// assert_eq!(crash.backtrace.len(), 2);
+/// assert_eq!(crash.backtrace[0].function, None);
+/// assert_eq!(crash.backtrace[1].function.as_deref(), Some("example::main::{{closure}}"));
+/// assert_eq!(crash.backtrace[1].file.as_deref(), Some("src/main.rs"));
+/// assert_eq!(crash.backtrace[1].line, Some(36));
+/// ```
+pub fn parse(text: &str) -> Vec<CrashReport> {
+    let mut crashes = Vec::new();
+    let mut current: Option<CrashReport> = None;
+    let mut in_stack = false;
+
+    for line in text.lines() {
+        if line == "CRASH:" {
+            crashes.extend(current.take());
+            current = Some(CrashReport::default());
+            in_stack = false;
+            continue;
+        }
+        if line.starts_with("=====") {
+            crashes.extend(current.take());
+            in_stack = false;
+            continue;
+        }
+        let crash = match current.as_mut() {
+            Some(crash) => crash,
+            None => continue, // session header
+        };
+
+        if in_stack {
+            if let Some(frame) = parse_frame(line) {
+                crash.backtrace.push(frame);
+                continue;
+            }
+            in_stack = false;
+        }
+
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key, value.trim()),
+            None => continue,
+        };
+        let text = || (!value.is_empty()).then(|| value.to_string());
+        match key {
+            "DESCRIPTION" => crash.description = text(),
+            "ORIG_FNAME" => crash.original_file = text(),
+            "FUZZ_FNAME" => crash.crash_file = text(),
+            "PID" => crash.pid = value.parse().ok(),
+            "SIGNAL" => {
+                // `SIGSEGV (11)`
+                let (name, number) = value.split_once(" (").unwrap_or((value, ""));
+                crash.signal = (!name.is_empty()).then(|| name.to_string());
+                crash.signal_number = number.trim_end_matches(')').parse().ok();
+            }
+            "PC" => crash.pc = parse_hex(value),
+            "FAULT ADDRESS" => crash.fault_address = text(),
+            "INSTRUCTION" => crash.instruction = text(),
+            "STACK HASH" => crash.stack_hash = parse_hex(value),
+            "STACK" => in_stack = true,
+            _ => {}
+        }
+    }
+    crashes.extend(current);
+
+    crashes
+}
+
+/// Read and parse a `HONGGFUZZ.REPORT.TXT` file.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<CrashReport>> {
+    Ok(parse(&fs::read_to_string(path)?))
+}
+
+fn parse_hex(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u64::from_str_radix(value, 16).ok()
+}
+
+// ` <0x000055555555e2b5> [func:NAME file:FILE line:N module:MODULE]`
+fn parse_frame(line: &str) -> Option<Frame> {
+    let line = line.trim().strip_prefix('<')?;
+    let (pc, rest) = line.split_once('>')?;
+    let rest = rest.trim();
+    let rest = rest.strip_prefix('[').unwrap_or(rest);
+    let rest = rest.strip_suffix(']').unwrap_or(rest);
+
+    // function names may contain spaces (`<T as Trait>::f`), so split on the known keys
+    let (function, rest) = field(rest, "func:", " file:");
+    let (file, rest) = field(rest, "file:", " line:");
+    let (line, rest) = field(rest, "line:", " module:");
+    let (module, _) = field(rest, "module:", "\0");
+
+    Some(Frame {
+        pc: parse_hex(pc)?,
+        function: function.filter(|f| *f != "UNKNOWN").map(String::from),
+        file: file.map(String::from),
+        line: line.and_then(|l| l.parse().ok()).filter(|l| *l != 0),
+        module: module.map(String::from),
+    })
+}
+
+// Split `key:value<next>rest` into `value` and `<next>rest`.
+fn field<'a>(s: &'a str, key: &str, next: &str) -> (Option<&'a str>, &'a str) {
+    let s = match s.strip_prefix(key) {
+        Some(s) => s,
+        None => return (None, s),
+    };
+    let (value, rest) = match s.find(next) {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, ""),
+    };
+    let value = value.trim();
+    ((!value.is_empty()).then_some(value), rest)
+}
+
+impl Frame {
+    /// Serialize the frame as a JSON object.
+    pub fn to_json(&self) -> String {
+        json::object([
+            ("pc", json::string(&format!("{:#x}", self.pc))),
+            ("function", json::opt_string(self.function.as_deref())),
+            ("file", json::opt_string(self.file.as_deref())),
+            ("line", self.line.map_or_else(|| "null".into(), |l| l.to_string())),
+            ("module", json::opt_string(self.module.as_deref())),
+        ])
+    }
+}
+
+impl CrashReport {
+    /// Serialize the crash as a JSON object.
+    ///
+    /// Addresses and hashes are serialized as hexadecimal strings since they
+    /// don't fit in a JSON number.
+    pub fn to_json(&self) -> String {
+        let hex = |v: Option<u64>| {
+            v.map_or_else(|| "null".into(), |v| json::string(&format!("{:#x}", v)))
+        };
+        json::object([
+            ("crash_file", json::opt_string(self.crash_file.as_deref())),
+            ("original_file", json::opt_string(self.original_file.as_deref())),
+            ("description", json::opt_string(self.description.as_deref())),
+            ("pid", self.pid.map_or_else(|| "null".into(), |p| p.to_string())),
+            ("signal", json::opt_string(self.signal.as_deref())),
+            (
+                "signal_number",
+                self.signal_number
+                    .map_or_else(|| "null".into(), |s| s.to_string()),
+            ),
+            ("pc", hex(self.pc)),
+            ("fault_address", json::opt_string(self.fault_address.as_deref())),
+            ("instruction", json::opt_string(self.instruction.as_deref())),
+            (
+                "stack_hash",
+                self.stack_hash.map_or_else(
+                    || "null".into(),
+                    |h| json::string(&format!("{:016x}", h)),
+                ),
+            ),
+            (
+                "backtrace",
+                json::array(self.backtrace.iter().map(Frame::to_json)),
+            ),
+        ])
+    }
+}
+
+/// Serialize crashes as a JSON array.
+pub fn to_json(crashes: &[CrashReport]) -> String {
+    json::array(crashes.iter().map(CrashReport::to_json))
+}
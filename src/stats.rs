@@ -0,0 +1,136 @@
+//! Parser for the statistics file honggfuzz writes when run with `--statsfile`.
+//!
+//! `cargo hfuzz run --stats-file TARGET` asks honggfuzz to write it in the
+//! target's workspace, and `cargo hfuzz stats TARGET` reads it back. Honggfuzz
+//! appends a comma-separated line each time it finds new coverage, so the last
+//! line can be old during a plateau. The file starts with a `#` header:
+//!
+//! ```text
+//! # unix_time, last_cov_update, total_exec, exec_per_sec, crashes, unique_crashes, hangs, edge_cov, block_cov
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::json;
+
+/// Name of the statistics file in the honggfuzz workspace (`-W`).
+pub const STATS_FILENAME: &str = "HONGGFUZZ.STATS.CSV";
+
+/// A snapshot of the campaign statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Unix time at which the snapshot was written.
+    pub unix_time: u64,
+    /// Unix time of the last new coverage.
+    pub last_cov_update: u64,
+    /// Total number of executions.
+    pub total_exec: u64,
+    /// Average executions per second since the start of the campaign.
+    pub exec_per_sec: u64,
+    /// Number of crashes.
+    pub crashes: u64,
+    /// Number of unique crashes.
+    pub unique_crashes: u64,
+    /// Number of timeouts.
+    pub hangs: u64,
+    /// Number of edges covered.
+    pub edge_cov: u64,
+    /// Number of blocks (PCs) covered.
+    pub block_cov: u64,
+}
+
+impl Stats {
+    /// Parse a single line of the statistics file.
+    ///
+    /// ```
+    /// use honggfuzz::stats::Stats;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let stats = Stats::parse_line("1700000042, 1700000040, 123456, 2939, 3, 1, 0, 512, 380").unwrap();
+    /// assert_eq!(stats.exec_per_sec, 2939);
+    /// assert_eq!(stats.unique_crashes, 1);
+    /// assert_eq!(stats.edge_cov, 512);
+    ///
+    /// let now = UNIX_EPOCH + Duration::from_secs(1700000100);
+    /// assert_eq!(stats.since_last_cov(now).as_secs(), 60);
+    ///
+    /// assert_eq!(Stats::parse_line("# unix_time, last_cov_update, ..."), None);
+    /// ```
+    pub fn parse_line(line: &str) -> Option<Stats> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let fields = line
+            .split(',')
+            .map(|f| f.trim().parse())
+            .collect::<Result<Vec<u64>, _>>()
+            .ok()?;
+        match fields[..] {
+            [
+                unix_time,
+                last_cov_update,
+                total_exec,
+                exec_per_sec,
+                crashes,
+                unique_crashes,
+                hangs,
+                edge_cov,
+                block_cov,
+                ..,
+            ] => Some(Stats {
+                unix_time,
+                last_cov_update,
+                total_exec,
+                exec_per_sec,
+                crashes,
+                unique_crashes,
+                hangs,
+                edge_cov,
+                block_cov,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Time elapsed between the last new coverage and `now`, usually
+    /// `SystemTime::now()`. Snapshots are only written on new coverage, so
+    /// `unix_time` can't be used for this.
+    pub fn since_last_cov(&self, now: SystemTime) -> Duration {
+        let now = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Duration::from_secs(now.saturating_sub(self.last_cov_update))
+    }
+
+    /// Serialize the snapshot as a JSON object.
+    pub fn to_json(&self) -> String {
+        json::object([
+            ("unix_time", self.unix_time.to_string()),
+            ("last_cov_update", self.last_cov_update.to_string()),
+            ("total_exec", self.total_exec.to_string()),
+            ("exec_per_sec", self.exec_per_sec.to_string()),
+            ("crashes", self.crashes.to_string()),
+            ("unique_crashes", self.unique_crashes.to_string()),
+            ("hangs", self.hangs.to_string()),
+            ("edge_cov", self.edge_cov.to_string()),
+            ("block_cov", self.block_cov.to_string()),
+        ])
+    }
+}
+
+/// Parse every snapshot of a statistics file, oldest first.
+pub fn parse(text: &str) -> Vec<Stats> {
+    text.lines().filter_map(Stats::parse_line).collect()
+}
+
+/// Read the most recent snapshot of a statistics file.
+///
+/// Returns `Ok(None)` if honggfuzz did not write any snapshot yet.
+pub fn read_last<P: AsRef<Path>>(path: P) -> io::Result<Option<Stats>> {
+    Ok(parse(&fs::read_to_string(path)?).pop())
+}
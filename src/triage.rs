@@ -0,0 +1,341 @@
+//! Crash triage helpers used by `cargo hfuzz triage`.
+//!
+//! A crash file is replayed against the `fuzzing_debug` build of a target, its
+//! panic output is parsed into a [`Crash`], and crashes sharing the same panic
+//! location and top stack frames are merged into a [`Group`].
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::json;
+
+/// Default number of symbolized frames used to tell crashes apart.
+pub const DEFAULT_FRAMES: usize = 3;
+
+/// How the replay of a crash file ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    /// The target panicked (exit status 101).
+    Panic,
+    /// The target was killed by a signal, usually from `unsafe` code.
+    Signal(i32),
+    /// The target exited with an unexpected status.
+    Exit(i32),
+    /// The target was killed after running longer than the timeout.
+    Timeout,
+    /// The input did not crash the debug build (exit status 2).
+    NoCrash,
+}
+
+impl Outcome {
+    /// Classify the exit of a replay from its exit code or terminating signal.
+    pub fn from_status(code: Option<i32>, signal: Option<i32>) -> Outcome {
+        match (code, signal) {
+            (Some(101), _) => Outcome::Panic,
+            (Some(2), _) => Outcome::NoCrash,
+            (Some(code), _) => Outcome::Exit(code),
+            (None, Some(signal)) => Outcome::Signal(signal),
+            (None, None) => Outcome::Exit(-1),
+        }
+    }
+
+    /// Short human readable name, e.g. `panic` or `SIGSEGV`.
+    pub fn name(&self) -> String {
+        match *self {
+            Outcome::Panic => "panic".into(),
+            Outcome::Signal(signal) => signal_name(signal),
+            Outcome::Exit(code) => format!("exit {}", code),
+            Outcome::Timeout => "timeout".into(),
+            Outcome::NoCrash => "no crash".into(),
+        }
+    }
+}
+
+/// Name of the common fatal signals, falls back to `signal N`.
+pub fn signal_name(signal: i32) -> String {
+    match signal {
+        4 => "SIGILL".into(),
+        5 => "SIGTRAP".into(),
+        6 => "SIGABRT".into(),
+        8 => "SIGFPE".into(),
+        9 => "SIGKILL".into(),
+        11 => "SIGSEGV".into(),
+        _ => format!("signal {}", signal),
+    }
+}
+
+/// Panic location, message and stack frames extracted from a replay's stderr.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PanicInfo {
+    /// `file:line:column` of the panic.
+    pub location: Option<String>,
+    /// Panic message.
+    pub message: Option<String>,
+    /// Symbolized frames, innermost first, with runtime frames removed.
+    pub frames: Vec<String>,
+}
+
+impl PanicInfo {
+    /// Parse the stderr of a target built with `cargo hfuzz build-debug` and run
+    /// with `RUST_BACKTRACE` set.
+    ///
+    /// ```
+    /// use honggfuzz::triage::PanicInfo;
+    ///
+    /// let stderr = "\
+    /// thread 'main' panicked at src/main.rs:36:13:
+    /// BOOM
+    /// stack backtrace:
+    ///    0: rust_begin_unwind
+    ///    1: core::panicking::panic_fmt
+    ///    2: example::main::{{closure}}
+    ///              at ./src/main.rs:36:13
+    ///    3: honggfuzz::fuzz
+    ///    4: example::main::h0123456789abcdef
+    /// note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+    /// ";
+    /// let info = PanicInfo::parse(stderr);
+    /// assert_eq!(info.location.as_deref(), Some("src/main.rs:36:13"));
+    /// assert_eq!(info.message.as_deref(), Some("BOOM"));
+    /// assert_eq!(info.frames, ["example::main::{{closure}}", "example::main"]);
+    ///
+    /// // recent rustc versions also print the thread id
+    /// let stderr = "\
+    /// thread 'main' (2441) panicked at src/main.rs:1:46:
+    /// index out of bounds: the len is 3 but the index is 3
+    /// stack backtrace:
+    ///    0: __rustc::rust_begin_unwind
+    ///    1: core::panicking::panic_fmt
+    ///    2: core::panicking::panic_bounds_check
+    ///    3: <usize as core::slice::index::SliceIndex<[T]>>::index
+    ///    4: example::main::{{closure}}
+    ///              at ./src/main.rs:1:46
+    /// ";
+    /// let info = PanicInfo::parse(stderr);
+    /// assert_eq!(info.location.as_deref(), Some("src/main.rs:1:46"));
+    /// assert_eq!(
+    ///     info.message.as_deref(),
+    ///     Some("index out of bounds: the len is 3 but the index is 3")
+    /// );
+    /// assert_eq!(info.frames, ["example::main::{{closure}}"]);
+    ///
+    /// // panic messages from rustc < 1.73
+    /// let info = PanicInfo::parse("thread 'main' panicked at 'BOOM', src/main.rs:36:13\n");
+    /// assert_eq!(info.location.as_deref(), Some("src/main.rs:36:13"));
+    /// assert_eq!(info.message.as_deref(), Some("BOOM"));
+    /// ```
+    pub fn parse(stderr: &str) -> PanicInfo {
+        let mut info = PanicInfo::default();
+        let mut lines = stderr.lines().peekable();
+
+        while let Some(line) = lines.next() {
+            if info.location.is_none() && line.starts_with("thread '") {
+                let rest = match panic_location(line) {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                if let Some(location) = rest.strip_suffix(':') {
+                    // rustc >= 1.73: the message is on the following lines
+                    info.location = Some(location.to_string());
+                    let mut message = Vec::new();
+                    while let Some(line) = lines.peek() {
+                        if line.starts_with("note: ") || line.starts_with("stack backtrace:") {
+                            break;
+                        }
+                        message.push(lines.next().unwrap());
+                    }
+                    info.message = Some(message.join("\n"));
+                } else if let Some((message, location)) = rest.rsplit_once("', ") {
+                    // rustc < 1.73: `panicked at 'message', location`
+                    info.location = Some(location.to_string());
+                    info.message = Some(message.trim_start_matches('\'').to_string());
+                } else {
+                    info.location = Some(rest.to_string());
+                }
+            } else if line.starts_with("stack backtrace:") {
+                info.frames = parse_frames(&mut lines);
+            }
+        }
+
+        info
+    }
+}
+
+// Everything after `panicked at ` in `thread 'main' panicked at ...`, recent rustc
+// versions also print the thread id: `thread 'main' (2441) panicked at ...`.
+fn panic_location(line: &str) -> Option<&str> {
+    let (_, rest) = line.strip_prefix("thread '")?.split_once("' ")?;
+    let rest = match rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(") "))
+    {
+        Some((tid, rest)) if tid.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => rest,
+    };
+    rest.strip_prefix("panicked at ")
+}
+
+fn parse_frames<'a, I>(lines: &mut std::iter::Peekable<I>) -> Vec<String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut frames = Vec::new();
+    while let Some(line) = lines.peek() {
+        let line = line.trim_start();
+        if line.starts_with("at ") {
+            lines.next();
+            continue;
+        }
+        let name = match line.split_once(": ") {
+            Some((index, name)) if index.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => break,
+        };
+        lines.next();
+        // with RUST_BACKTRACE=full, frames look like `0x55d1c3a4b5c6 - example::main::h0123456789abcdef`
+        let name = name.split_once(" - ").map_or(name, |(_, name)| name);
+        let name = strip_hash(name);
+        if !is_runtime_frame(name) {
+            frames.push(name.to_string());
+        }
+    }
+    frames
+}
+
+// Remove the `::h0123456789abcdef` suffix rustc appends to legacy symbol names.
+fn strip_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((prefix, hash))
+            if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            prefix
+        }
+        _ => name,
+    }
+}
+
+// Frames from the standard library, the panic machinery or honggfuzz itself
+// are the same for every crash and would only add noise to the signature.
+fn is_runtime_frame(name: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "std::",
+        "core::",
+        "alloc::",
+        "<std::",
+        "<core::",
+        "<alloc::",
+        "honggfuzz::",
+        "rust_begin_unwind",
+        "rust_panic",
+        "__rust",
+        "__libc_start",
+        "_start",
+        "<unknown>",
+    ];
+    // trait impls of the standard library, e.g. `<usize as core::slice::index::SliceIndex<[T]>>::index`
+    const TRAITS: &[&str] = &[" as std::", " as core::", " as alloc::"];
+    name == "main"
+        || PREFIXES.iter().any(|p| name.starts_with(p))
+        || (name.starts_with('<') && TRAITS.iter().any(|t| name.contains(t)))
+}
+
+/// A single replayed crash file.
+#[derive(Clone, Debug)]
+pub struct Crash {
+    /// Path of the crash file.
+    pub path: PathBuf,
+    /// Size of the crash file in bytes.
+    pub size: u64,
+    /// How the replay ended.
+    pub outcome: Outcome,
+    /// What could be parsed from the replay's stderr.
+    pub panic: PanicInfo,
+}
+
+impl Crash {
+    /// Key used to decide whether two crashes are the same bug.
+    pub fn signature(&self, frames: usize) -> (Outcome, Option<String>, Vec<String>) {
+        // the panic message often contains input dependent values, so it is not part of the signature
+        (
+            self.outcome,
+            self.panic.location.clone(),
+            self.panic.frames.iter().take(frames).cloned().collect(),
+        )
+    }
+}
+
+/// Crashes sharing the same signature.
+#[derive(Clone, Debug)]
+pub struct Group {
+    /// How the replays ended.
+    pub outcome: Outcome,
+    /// Panic location shared by the crashes.
+    pub location: Option<String>,
+    /// Top frames shared by the crashes.
+    pub frames: Vec<String>,
+    /// Crashes of the group, smallest first.
+    pub crashes: Vec<Crash>,
+}
+
+impl Group {
+    /// Smallest reproducer of the group.
+    pub fn smallest(&self) -> &Crash {
+        &self.crashes[0]
+    }
+
+    /// Panic message of the smallest reproducer.
+    pub fn message(&self) -> Option<&str> {
+        self.smallest().panic.message.as_deref()
+    }
+
+    /// Serialize the group as a JSON object.
+    pub fn to_json(&self) -> String {
+        let smallest = self.smallest();
+        json::object([
+            ("outcome", json::string(&self.outcome.name())),
+            ("location", json::opt_string(self.location.as_deref())),
+            ("message", json::opt_string(self.message())),
+            ("frames", json::string_array(&self.frames)),
+            ("count", self.crashes.len().to_string()),
+            (
+                "smallest",
+                json::string(&smallest.path.display().to_string()),
+            ),
+            ("smallest_size", smallest.size.to_string()),
+            (
+                "crashes",
+                json::string_array(self.crashes.iter().map(|c| c.path.display().to_string())),
+            ),
+        ])
+    }
+}
+
+/// Group crashes by outcome, panic location and their top `frames` frames.
+///
+/// Groups are sorted by decreasing number of crashes, crashes inside a group by
+/// increasing size so that the first one is the smallest reproducer.
+pub fn group(crashes: Vec<Crash>, frames: usize) -> Vec<Group> {
+    let mut groups = BTreeMap::<_, Vec<Crash>>::new();
+    for crash in crashes {
+        groups.entry(crash.signature(frames)).or_default().push(crash);
+    }
+
+    let mut groups: Vec<_> = groups
+        .into_iter()
+        .map(|((outcome, location, frames), mut crashes)| {
+            crashes.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| a.path.cmp(&b.path)));
+            Group {
+                outcome,
+                location,
+                frames,
+                crashes,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.crashes.len()));
+    groups
+}
+
+/// Serialize groups as a JSON array.
+pub fn to_json(groups: &[Group]) -> String {
+    json::array(groups.iter().map(Group::to_json))
+}
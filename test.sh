@@ -11,7 +11,7 @@ version=`rustc --version`
 if [ -n "${version##*1.47*}" ] ;then
 	cargo build --release --verbose
 else
-	cargo build --release --verbose --no-default-features
+	cargo build --release --verbose --no-default-features --features cli
 fi
 export CARGO_HFUZZ="$(pwd)/target/release/cargo-hfuzz hfuzz" # force examples' tests to use this version
 $CARGO_HFUZZ version # record version for logs
@@ -33,7 +33,8 @@ if [ -z "${version##*nightly*}" ] ;then
 		RUSTFLAGS="-Z sanitizer=thread -Cunsafe-allow-abi-mismatch=sanitizer" ./test.sh # not working on macos
 		RUSTFLAGS="-Z sanitizer=leak -Cunsafe-allow-abi-mismatch=sanitizer" ./test.sh # the leak sanitizer is only available on Linux
 	fi
-	# RUSTFLAGS="-Z sanitizer=memory" ./test.sh # not working, see: https://github.com/rust-lang/rust/issues/39610
+	# RUSTFLAGS="-Z sanitizer=memory" ./test.sh # not working without rebuilding std, see: https://github.com/rust-lang/rust/issues/39610
+	# use `cargo hfuzz run --sanitizer memory` instead, which builds with `-Zbuild-std`
 fi
 
 # go back to root crate
@@ -46,10 +47,10 @@ if [ -n "${version##*1.47*}" ] ;then
 	# run unit tests
 	cargo test
 else
-	cargo doc --no-default-features
+	cargo doc --no-default-features --features cli
 
 	# run unit tests
-	cargo test --no-default-features
+	cargo test --no-default-features --features cli
 fi
 
 cargo clean